@@ -1,13 +1,24 @@
+use std::fs;
 use std::path::PathBuf;
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+#[cfg(target_os = "windows")]
 use glob::glob;
+use ncmdump::tag::{Id3Version, TagMap, TagPolicy};
+
 use crate::errors::Error;
+use crate::inspect::Inspect;
+use crate::name::NameTemplate;
+use crate::sanitize::{parse_replacement, Replacement};
 
 #[derive(Clone, Debug, Default, Parser)]
 #[command(name = "ncmdump", bin_name = "ncmdump", about, version)]
+#[command(args_conflicts_with_subcommands = true)]
 pub(crate) struct Command {
+    #[command(subcommand)]
+    pub(crate) subcommand: Option<Subcommands>,
+
     /// Specified the files to convert.
     #[arg(value_name = "FILES")]
     pub(crate) matchers: Vec<String>,
@@ -21,12 +32,162 @@ pub(crate) struct Command {
     #[arg(short = 'v', long = "verbose")]
     pub(crate) verbose: bool,
 
+    /// Don't write the `163 key` string into the comment tag.
+    /// The netease client use it to recognise the converted music.
+    #[arg(long = "no-comment")]
+    pub(crate) no_comment: bool,
+
+    /// Recover the audio from truncated or partially downloaded ncm files.
+    /// The missing metadata is skipped and a warning is printed.
+    #[arg(long = "salvage")]
+    pub(crate) salvage: bool,
+
+    /// Specified the template of the output file name, like `{artist} - {title}` or
+    /// `{album_artist}/{album}/{title}`. The variables are the tag fields, which can be
+    /// indexed like `{artist[0]}`, fall back like `{album_artist|artist}` and be
+    /// truncated like `{title:40}`. It can be repeated, the first one whose variables
+    /// are all present is used, or the name of input file is kept.
+    #[arg(long = "name-template", value_name = "TEMPLATE", value_parser = NameTemplate::parse)]
+    pub(crate) name_templates: Vec<NameTemplate>,
+
+    /// Specified the separator to join the multiple artists in the file name.
+    /// Default it's `, `.
+    #[arg(long = "name-separator", value_name = "SEPARATOR")]
+    pub(crate) name_separator: Option<String>,
+
+    /// Specified the replacement of the reserved characters like `:` and `?` in the
+    /// file name, or `fullwidth` to use the full-width look-alikes like `：`.
+    /// Default it's `_`.
+    #[arg(long = "name-replacement", value_name = "REPLACEMENT", value_parser = parse_replacement)]
+    pub(crate) name_replacement: Option<Replacement>,
+
+    /// Transliterate the file name into ascii for the old devices, like `Han Ya Shao Nian`.
+    #[arg(long = "ascii-names")]
+    pub(crate) ascii_names: bool,
+
+    /// Specified the maximum bytes of each component of the output path.
+    /// Default it's 255, the longer names are truncated.
+    #[arg(
+        long = "max-name-bytes",
+        value_name = "BYTES",
+        value_parser = clap::value_parser!(u16).range(16..=255),
+    )]
+    pub(crate) max_name_bytes: Option<u16>,
+
+    /// Specified the maximum bytes of the absolute output path.
+    /// Default it's 4095, the file name is shortened to fit it.
+    #[arg(
+        long = "max-path-bytes",
+        value_name = "BYTES",
+        value_parser = clap::value_parser!(u16).range(64..),
+    )]
+    pub(crate) max_path_bytes: Option<u16>,
+
+    /// Specified the toml file which maps the information into the tags.
+    /// The fields are rendered by templates like `{name} ({alias[0]})`.
+    #[arg(long = "tag-map", value_name = "FILE")]
+    pub(crate) tag_map: Option<PathBuf>,
+
+    /// Specified the separator to join the multiple artists.
+    /// It's used by the tags which can't store multiple values. Default it's `/`.
+    #[arg(long = "artist-separator", value_name = "SEPARATOR")]
+    pub(crate) artist_separator: Option<String>,
+
+    /// Specified the version of id3v2 tag in the mp3 music, `2.3` or `2.4`.
+    /// Default it's `2.4`, whose multiple values are separated by null.
+    #[arg(long = "id3-version", value_name = "VERSION", value_parser = parse_id3_version)]
+    pub(crate) id3_version: Option<Id3Version>,
+
+    /// Append the id3v1 tag to the mp3 music for the legacy devices.
+    #[arg(long = "id3v1")]
+    pub(crate) id3v1: bool,
+
+    /// Specified the policy of the tags embedded in the music,
+    /// `keep`, `overwrite` or `fill-missing`.
+    /// Default it's `fill-missing` for the qmc music and `overwrite` for the others.
+    #[arg(long = "tag-policy", value_name = "POLICY", value_parser = parse_tag_policy)]
+    pub(crate) tag_policy: Option<TagPolicy>,
+
+    /// Replace the embedded cover and remove the ReplayGain and lyrics tags
+    /// when the tags are overwritten.
+    #[arg(long = "replace-extras")]
+    pub(crate) replace_extras: bool,
+
+    /// Don't embed the cover into the music.
+    #[arg(long = "no-cover")]
+    pub(crate) no_cover: bool,
+
+    /// Specified the maximum width and height of the cover.
+    /// The larger cover is scaled down with the aspect ratio kept.
+    #[arg(long = "cover-max-size", value_name = "PIXELS")]
+    pub(crate) cover_max_size: Option<u32>,
+
+    /// Re-encode the cover into jpeg with the quality, it should be 1 to 100.
+    #[arg(
+        long = "cover-quality",
+        value_name = "QUALITY",
+        value_parser = clap::value_parser!(u8).range(1..=100),
+    )]
+    pub(crate) cover_quality: Option<u8>,
+
+    /// Write the cover as a jpeg file into each output directory, like `cover.jpg`
+    /// or `folder.jpg`. The existing file isn't overwritten.
+    #[arg(long = "cover-file", value_name = "NAME")]
+    pub(crate) cover_file: Option<String>,
+
+    /// Specified the directory of the lrc lyrics, they're named by the stem of music
+    /// like `song.lrc` or `song.eng.lrc`.
+    /// Default it's the same directory with input file.
+    #[arg(long = "lyrics-dir", value_name = "DIR")]
+    pub(crate) lyrics_dir: Option<PathBuf>,
+
+    /// Specified the lyric cache directory of netease client, like
+    /// `%LOCALAPPDATA%/Netease/CloudMusic/webdata/lyric`. The cached lyrics of the
+    /// ncm music are found by the song id, and merged with the translation.
+    #[arg(long = "netease-lyric-dir", value_name = "DIR")]
+    pub(crate) netease_lyric_dir: Option<PathBuf>,
+
+    /// Embed the synchronised lyrics into the mp3 music besides the unsynchronised ones.
+    #[arg(long = "synced-lyrics")]
+    pub(crate) synced_lyrics: bool,
+
     /// The process work count.
     /// It should more than 0 and less than 9.
     #[arg(short = 'w', long = "worker", default_value = "1")]
     pub(crate) worker: usize,
 }
 
+/// Parse the version of id3v2 tag.
+fn parse_id3_version(version: &str) -> Result<Id3Version, String> {
+    match version {
+        "2.3" => Ok(Id3Version::V23),
+        "2.4" => Ok(Id3Version::V24),
+        _ => Err(format!(
+            "unsupported id3 version `{}`, use 2.3 or 2.4",
+            version
+        )),
+    }
+}
+
+/// Parse the policy of the embedded tags.
+fn parse_tag_policy(policy: &str) -> Result<TagPolicy, String> {
+    match policy {
+        "keep" => Ok(TagPolicy::Keep),
+        "overwrite" => Ok(TagPolicy::Overwrite),
+        "fill-missing" => Ok(TagPolicy::FillMissing),
+        _ => Err(format!(
+            "unsupported tag policy `{}`, use keep, overwrite or fill-missing",
+            policy
+        )),
+    }
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum Subcommands {
+    /// Print the layout of a ncm file.
+    Inspect(Inspect),
+}
+
 impl Command {
     pub(crate) fn invalid(&self) -> Result<(), Error> {
         // Check argument worker
@@ -42,6 +203,19 @@ impl Command {
         Ok(())
     }
 
+    /// Load the tag map from the file, it's empty if the file isn't specified.
+    pub(crate) fn load_tag_map(&self) -> Result<TagMap> {
+        let Some(path) = &self.tag_map else {
+            return Ok(TagMap::default());
+        };
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Can't read the tag map {}", path.display()))?;
+        let map: TagMap = toml::from_str(&content)
+            .with_context(|| format!("Can't parse the tag map {}", path.display()))?;
+        map.validate()?;
+        Ok(map)
+    }
+
     #[cfg(target_os = "windows")]
     pub(crate) fn items(&self) -> std::result::Result<Vec<PathBuf>, Error> {
         let mut paths = Vec::new();
@@ -73,9 +247,15 @@ impl Command {
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
     use anyhow::Result;
+    use clap::Parser;
+    use ncmdump::tag::{Id3Version, TagMap, TagPolicy, Template};
 
     use crate::errors::Error;
+    use crate::sanitize::Replacement;
     use crate::Command;
 
     #[test]
@@ -86,7 +266,102 @@ mod tests {
             ..Default::default()
         };
         let result = command.invalid();
-        assert!(result.is_err_and(|err| err == Error::NoFile));
+        assert!(result.is_err_and(|err| matches!(err, Error::NoFile)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_tag_map_ok() -> Result<()> {
+        let command = Command::default();
+        assert_eq!(command.load_tag_map()?, TagMap::default());
+
+        let path = std::env::temp_dir().join(format!("ncmdump-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "[fields]\ntitle = [\"{name} ({alias[0]})\", \"{name}\"]\n\n[id3]\nTIT3 = \"\"\n",
+        )?;
+        let command = Command {
+            tag_map: Some(path.clone()),
+            ..Default::default()
+        };
+        let map = command.load_tag_map()?;
+        assert_eq!(map.fields.len(), 1);
+        assert_eq!(map.id3.get("TIT3"), Some(&Template::Single(String::new())));
+
+        fs::write(&path, "[id3]\nAPIC = \"{name}\"\n")?;
+        assert!(command.load_tag_map().is_err());
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_tag_args_ok() -> Result<()> {
+        let command = Command::try_parse_from([
+            "ncmdump",
+            "--artist-separator",
+            "; ",
+            "--id3-version",
+            "2.3",
+            "--id3v1",
+            "--tag-policy",
+            "fill-missing",
+            "--replace-extras",
+            "--cover-max-size",
+            "500",
+            "--cover-quality",
+            "85",
+            "--cover-file",
+            "folder.jpg",
+            "--lyrics-dir",
+            "lyrics",
+            "--synced-lyrics",
+            "--netease-lyric-dir",
+            "cache",
+            "--name-template",
+            "{artist} - {title}",
+            "--name-template",
+            "{title}",
+            "--name-separator",
+            " & ",
+            "--name-replacement",
+            "fullwidth",
+            "--ascii-names",
+            "--max-name-bytes",
+            "128",
+            "--max-path-bytes",
+            "259",
+            "test.ncm",
+        ])?;
+        assert_eq!(command.artist_separator.as_deref(), Some("; "));
+        assert_eq!(command.id3_version, Some(Id3Version::V23));
+        assert!(command.id3v1);
+        assert_eq!(command.tag_policy, Some(TagPolicy::FillMissing));
+        assert!(command.replace_extras);
+        assert_eq!(command.cover_max_size, Some(500));
+        assert_eq!(command.cover_quality, Some(85));
+        assert_eq!(command.cover_file.as_deref(), Some("folder.jpg"));
+        assert_eq!(command.lyrics_dir, Some(PathBuf::from("lyrics")));
+        assert!(command.synced_lyrics);
+        assert_eq!(command.netease_lyric_dir, Some(PathBuf::from("cache")));
+        assert_eq!(command.name_templates.len(), 2);
+        assert_eq!(command.name_separator.as_deref(), Some(" & "));
+        assert_eq!(command.name_replacement, Some(Replacement::FullWidth));
+        assert!(command.ascii_names);
+        assert_eq!(command.max_name_bytes, Some(128));
+        assert_eq!(command.max_path_bytes, Some(259));
+
+        let result = Command::try_parse_from(["ncmdump", "--id3-version", "2.2", "test.ncm"]);
+        assert!(result.is_err());
+        let result = Command::try_parse_from(["ncmdump", "--tag-policy", "merge", "test.ncm"]);
+        assert!(result.is_err());
+        let result = Command::try_parse_from(["ncmdump", "--cover-quality", "0", "test.ncm"]);
+        assert!(result.is_err());
+        let result = Command::try_parse_from(["ncmdump", "--name-template", "{name}", "test.ncm"]);
+        assert!(result.is_err());
+        let result = Command::try_parse_from(["ncmdump", "--name-replacement", "?", "test.ncm"]);
+        assert!(result.is_err());
+        let result = Command::try_parse_from(["ncmdump", "--max-name-bytes", "300", "test.ncm"]);
+        assert!(result.is_err());
         Ok(())
     }
 
@@ -115,7 +390,7 @@ mod tests {
                 ..Default::default()
             };
             let result = command.invalid();
-            assert!(result.is_err_and(|err| err == Error::Worker));
+            assert!(result.is_err_and(|err| matches!(err, Error::Worker)));
         }
         Ok(())
     }
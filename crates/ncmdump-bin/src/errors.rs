@@ -4,10 +4,12 @@ use glob::{GlobError, PatternError};
 use ncmdump::error::Errors;
 use thiserror::Error;
 
-#[derive(Clone, Debug, Error, Eq, PartialEq)]
+#[derive(Debug, Error)]
 pub enum Error {
     #[error("Can't resolve the path")]
     Path,
+    #[error("The output path is too long")]
+    PathLength,
     #[error("Invalid file format")]
     Format,
     #[error("No file can be converted")]
@@ -16,8 +18,10 @@ pub enum Error {
     Metadata,
     #[error("Worker can't less than 0 and more than 8")]
     Worker,
-    #[error("Dump err: {0}")]
-    Dump(String),
+    #[error("IO error")]
+    Io(#[from] io::Error),
+    #[error("Dump error")]
+    Dump(#[from] Errors),
 }
 
 impl From<PatternError> for Error {
@@ -31,15 +35,3 @@ impl From<GlobError> for Error {
         Self::Path
     }
 }
-
-impl From<io::Error> for Error {
-    fn from(_: io::Error) -> Self {
-        Self::Path
-    }
-}
-
-impl From<Errors> for Error {
-    fn from(err: Errors) -> Self {
-        Error::Dump(err.to_string())
-    }
-}
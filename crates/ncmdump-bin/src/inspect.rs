@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use ncmdump::{NcmLayout, NcmSection, Ncmdump};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum Format {
+    /// Human readable table.
+    #[default]
+    Table,
+    /// Pretty printed json.
+    Json,
+}
+
+#[derive(Clone, Debug, Args)]
+pub(crate) struct Inspect {
+    /// Specified the ncm file to inspect.
+    #[arg(value_name = "FILE")]
+    pub(crate) file: PathBuf,
+
+    /// The output format.
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = Format::Table)]
+    pub(crate) format: Format,
+}
+
+impl Inspect {
+    pub(crate) fn run(&self) -> Result<()> {
+        let file = File::open(&self.file)
+            .with_context(|| format!("Can't open {}", self.file.display()))?;
+        let ncm = Ncmdump::from_reader(file)
+            .with_context(|| format!("Can't inspect {}", self.file.display()))?;
+        let layout = ncm.layout();
+        match self.format {
+            Format::Table => print!("{}", format_table(layout)),
+            Format::Json => println!("{}", serde_json::to_string_pretty(layout)?),
+        }
+        Ok(())
+    }
+}
+
+fn format_table(layout: &NcmLayout) -> String {
+    let sections: [(&str, &NcmSection); 9] = [
+        ("magic", &layout.magic),
+        ("version", &layout.version),
+        ("key", &layout.key),
+        ("info", &layout.info),
+        ("checksum", &layout.checksum),
+        ("gap", &layout.gap),
+        ("cover_frame", &layout.cover_frame),
+        ("image", &layout.image),
+        ("audio", &layout.audio),
+    ];
+    let mut table = format!("{:<12} {:>10} {:>10}\n", "SECTION", "OFFSET", "LENGTH");
+    for (name, section) in sections {
+        table.push_str(&format!(
+            "{:<12} {:>10} {:>10}\n",
+            name, section.offset, section.length
+        ));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use anyhow::Result;
+    use ncmdump::Ncmdump;
+
+    use super::format_table;
+
+    #[test]
+    fn test_format_table_ok() -> Result<()> {
+        let file = File::open("../ncmdump/res/test.ncm")?;
+        let ncm = Ncmdump::from_reader(file)?;
+        let table = format_table(ncm.layout());
+        let lines = table.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "SECTION          OFFSET     LENGTH");
+        assert_eq!(lines[3], "key                  14        128");
+        assert_eq!(lines[9], "audio             39682      61440");
+        Ok(())
+    }
+}
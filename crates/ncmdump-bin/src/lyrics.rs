@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use ncmdump::tag::Lyrics;
+use serde_json::Value;
+
+/// The keys of the original, translated and romanised lyrics in the lyric cache of
+/// netease client, the original one is `lrc` in the response of netease api.
+const NETEASE_LYRIC_KEYS: [&[&str]; 3] = [&["lyric", "lrc"], &["tlyric"], &["romalrc"]];
+
+/// List the lrc files in the directory, the directory is only listed once and the
+/// lyrics of each music are found in the listed files by [`find_lyrics`].
+pub(crate) fn list_lyrics(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Can't read the lyrics directory {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let lrc = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("lrc"));
+        if lrc && entry.file_type()?.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Find the lrc files of the music in the listed files, they're named by the stem of
+/// the music like `song.lrc`, or with a language like `song.eng.lrc`.
+///
+/// The language in the file name is used when the lyrics have no `[la:...]` tag,
+/// and the lyrics without any line are skipped.
+pub(crate) fn find_lyrics(files: &[PathBuf], stem: &str) -> Result<Vec<Lyrics>> {
+    let mut matched = Vec::new();
+    for path in files {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        if let Some(language) = match_name(&name.to_string_lossy(), stem) {
+            matched.push((language, path));
+        }
+    }
+    // The lyrics without language come first, the others are sorted by the language.
+    matched.sort();
+
+    let mut lyrics = Vec::new();
+    for (language, path) in matched {
+        let content =
+            fs::read(path).with_context(|| format!("Can't read the lyrics {}", path.display()))?;
+        let mut item = Lyrics::parse(&String::from_utf8_lossy(&content));
+        if item.lines.is_empty() {
+            continue;
+        }
+        if item.language.is_none() {
+            item.language = language;
+        }
+        lyrics.push(item);
+    }
+    Ok(lyrics)
+}
+
+/// Load the lyrics of the song from the lyric cache of netease client, the cache
+/// file is named by the song id like `123456` or `123456.json`.
+///
+/// The translated and romanised lyrics are merged into the original lyrics, so the
+/// lines of the same time are bilingual. It's none if the song isn't cached.
+pub(crate) fn load_netease_lyrics(dir: &Path, id: u64) -> Result<Option<Lyrics>> {
+    let names = [id.to_string(), format!("{}.json", id)];
+    let Some(path) = names
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|p| p.is_file())
+    else {
+        return Ok(None);
+    };
+    let content =
+        fs::read(&path).with_context(|| format!("Can't read the lyrics {}", path.display()))?;
+    let value: Value = serde_json::from_slice(&content)
+        .with_context(|| format!("Can't parse the lyrics {}", path.display()))?;
+
+    let mut lyrics = Lyrics::default();
+    for keys in NETEASE_LYRIC_KEYS {
+        let Some(item) = keys.iter().find_map(|key| value.get(key)) else {
+            continue;
+        };
+        // The lyric is the string in the cache, or the object in the api response.
+        let text = match item {
+            Value::Object(object) => object.get("lyric").and_then(Value::as_str),
+            item => item.as_str(),
+        };
+        if let Some(text) = text {
+            lyrics.merge(&Lyrics::parse(text));
+        }
+    }
+    Ok(Some(lyrics).filter(|lyrics| !lyrics.lines.is_empty()))
+}
+
+/// Match the file name of lyrics with the stem of music, the language in the file
+/// name is returned if it's matched.
+fn match_name(name: &str, stem: &str) -> Option<Option<String>> {
+    let (rest, ext) = name.rsplit_once('.')?;
+    if !ext.eq_ignore_ascii_case("lrc") {
+        return None;
+    }
+    if rest == stem {
+        return Some(None);
+    }
+    let language = rest.strip_prefix(stem)?.strip_prefix('.')?;
+    match !language.is_empty() && !language.contains('.') {
+        true => Some(Some(language.to_string())),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn test_match_name_ok() {
+        assert_eq!(match_name("song.lrc", "song"), Some(None));
+        assert_eq!(match_name("song.LRC", "song"), Some(None));
+        assert_eq!(
+            match_name("song.eng.lrc", "song"),
+            Some(Some("eng".to_string()))
+        );
+        assert_eq!(match_name("song.txt", "song"), None);
+        assert_eq!(match_name("song 2.lrc", "song"), None);
+        assert_eq!(match_name("song.a.b.lrc", "song"), None);
+        assert_eq!(match_name("song..lrc", "song"), None);
+    }
+
+    #[test]
+    fn test_find_lyrics_ok() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ncmdump-lyrics-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("song.lrc"), "[00:01.00]First\n[00:02.00]Second")?;
+        fs::write(dir.join("song.chi.lrc"), "[00:01.00]第一\n[00:02.00]第二")?;
+        fs::write(dir.join("song.jpn.lrc"), "[la:JPN]\nプレーン")?;
+        fs::write(dir.join("song.empty.lrc"), "[ar:Artist]")?;
+        fs::write(dir.join("other.lrc"), "[00:01.00]Other")?;
+        fs::write(dir.join("song.txt"), "[00:01.00]Text")?;
+        fs::create_dir_all(dir.join("song.eng.lrc"))?;
+
+        let files = list_lyrics(&dir)?;
+        assert_eq!(files.len(), 5);
+        let lyrics = find_lyrics(&files, "song")?;
+        let languages = lyrics
+            .iter()
+            .map(|lyrics| lyrics.language.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(languages, [None, Some("chi"), Some("JPN")]);
+        assert_eq!(lyrics[0].to_text(), "First\nSecond");
+        assert!(!lyrics[2].is_synced());
+        assert!(find_lyrics(&files, "missing")?.is_empty());
+        fs::remove_dir_all(&dir)?;
+        assert!(list_lyrics(&dir).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_netease_lyrics_ok() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ncmdump-netease-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join("1"),
+            r#"{"lyric":"[00:01.00]一\n[00:02.00]二","tlyric":"[by:someone]\n[00:01.00]One","romalrc":"[00:02.00]ni"}"#,
+        )?;
+        fs::write(
+            dir.join("2.json"),
+            r#"{"lrc":{"version":1,"lyric":"[00:01.00]Line"},"tlyric":{"version":0,"lyric":""}}"#,
+        )?;
+        fs::write(dir.join("3"), r#"{"nolyric":true}"#)?;
+        fs::write(dir.join("4"), "invalid")?;
+
+        let lyrics = load_netease_lyrics(&dir, 1)?.unwrap();
+        assert_eq!(
+            lyrics.to_lrc(),
+            "[00:01.00]一\n[00:01.00]One\n[00:02.00]二\n[00:02.00]ni"
+        );
+        let lyrics = load_netease_lyrics(&dir, 2)?.unwrap();
+        assert_eq!(lyrics.to_text(), "Line");
+        assert_eq!(load_netease_lyrics(&dir, 3)?, None);
+        assert!(load_netease_lyrics(&dir, 4).is_err());
+        assert_eq!(load_netease_lyrics(&dir, 5)?, None);
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
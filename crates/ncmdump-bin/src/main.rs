@@ -1,44 +1,101 @@
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 
-use ncmdump::{Ncmdump, QmcDump};
-use ncmdump::error::Errors;
+use ncmdump::error::Section;
+use ncmdump::tag::{
+    self, Container, CoverOptions, Dump, Field, Lyrics, TagOptions, TagPolicy, Tags,
+};
 use ncmdump::utils::FileType;
+use ncmdump::{CancelToken, Ncmdump, QmcDump, SalvageReport};
 
-use crate::command::Command;
+use crate::command::{Command, Subcommands};
 use crate::errors::Error;
-use crate::metadata::{FlacMetadata, Metadata, Mp3Metadata};
 use crate::provider::{DataProvider, FileProvider};
+use crate::sanitize::SanitizeOptions;
 use crate::state::State;
 
 mod command;
 mod errors;
-mod metadata;
+mod inspect;
+mod lyrics;
+mod name;
 mod provider;
+mod sanitize;
 mod state;
-mod utils;
 
 /// The global program
 #[derive(Clone)]
 struct Program {
     command: Arc<Command>,
     state: Arc<State>,
+    tag_options: Arc<TagOptions>,
+    sanitize_options: Arc<SanitizeOptions>,
+    /// The directories whose cover file is already written
+    cover_dirs: Arc<Mutex<HashSet<PathBuf>>>,
+    /// The output paths which are claimed by the converted files
+    target_paths: Arc<Mutex<HashSet<PathBuf>>>,
+    /// The lrc files of the directories which are already listed
+    lyric_files: Arc<Mutex<HashMap<PathBuf, Arc<Vec<PathBuf>>>>>,
 }
 
 impl Program {
     /// Create new command progress.
     fn new(command: Command) -> Result<Self> {
         let state = State::try_from(&command)?;
-        Ok(Self {
+        let default = TagOptions::default();
+        let tag_options = TagOptions {
+            comment: !command.no_comment,
+            map: command.load_tag_map()?,
+            separator: command
+                .artist_separator
+                .clone()
+                .unwrap_or(default.separator),
+            id3_version: command.id3_version.unwrap_or(default.id3_version),
+            id3v1: command.id3v1,
+            keep_extras: !command.replace_extras,
+            synced_lyrics: command.synced_lyrics,
+            cover: CoverOptions {
+                embed: !command.no_cover,
+                max_size: command.cover_max_size,
+                quality: command.cover_quality,
+            },
+            ..default
+        };
+        let default = SanitizeOptions::default();
+        let sanitize_options = SanitizeOptions {
+            replacement: command
+                .name_replacement
+                .clone()
+                .unwrap_or(default.replacement),
+            ascii: command.ascii_names,
+            max_name_bytes: command
+                .max_name_bytes
+                .map_or(default.max_name_bytes, usize::from),
+            max_path_bytes: command
+                .max_path_bytes
+                .map_or(default.max_path_bytes, usize::from),
+        };
+        let program = Self {
             command: Arc::new(command),
             state: Arc::new(state),
-        })
+            tag_options: Arc::new(tag_options),
+            sanitize_options: Arc::new(sanitize_options),
+            cover_dirs: Arc::new(Mutex::new(HashSet::new())),
+            target_paths: Arc::new(Mutex::new(HashSet::new())),
+            lyric_files: Arc::new(Mutex::new(HashMap::new())),
+        };
+        // The lyrics directory is listed up front, so it fails before any conversion.
+        if let Some(dir) = &program.command.lyrics_dir {
+            program.list_lyrics(dir)?;
+        }
+        Ok(program)
     }
 
     fn dump<P>(&self, provider: &P) -> Result<()>
@@ -46,70 +103,316 @@ impl Program {
         P: DataProvider,
     {
         let source = File::open(provider.get_path())?;
+        let progress = self.state.create_progress(provider)?;
+        let cancel = CancelToken::new();
+        let mut ncm = None;
         let data = match provider.get_format() {
-            FileType::Ncm => self.get_data(Ncmdump::from_reader(source)?, provider),
-            FileType::Qmc => self.get_data(QmcDump::from_reader(source)?, provider),
-            FileType::Other => Err(Error::Format.into()),
-        }?;
-        let ext = match data[..4] {
-            [0x66, 0x4C, 0x61, 0x43] => Ok("flac"),
-            [0x49, 0x44, 0x33, _] => Ok("mp3"),
-            _ => Err(Error::Format),
-        }?;
+            FileType::Ncm => {
+                let (mut dump, report) = self.open_ncm(source, provider)?;
+                let data = dump.get_data_with_progress(&progress, &cancel)?;
+                ncm = Some((dump, report));
+                data
+            }
+            FileType::Qmc => {
+                QmcDump::from_reader(source)?.get_data_with_progress(&progress, &cancel)?
+            }
+            FileType::Other => return Err(Error::Format.into()),
+        };
+        progress.finish();
+        let ext = Container::sniff(&data).ok_or(Error::Format)?.extension();
 
+        let (mut tags, report) = match ncm {
+            Some((mut dump, report)) => (
+                self.get_ncm_tags(&mut dump, report.as_ref(), &provider.get_name())?,
+                report,
+            ),
+            None => {
+                let tags = Tags {
+                    info: Some(provider.get_name_info()),
+                    ..Default::default()
+                };
+                (tags, None)
+            }
+        };
         let path = provider.get_path();
-        let target_path = match &self.command.output {
-            None => path.with_extension(ext),
-            Some(p) => Path::new(p)
-                .join(
-                    path.file_name()
-                        .ok_or(Errors::IO("Can't get file name".into()))?,
-                )
-                .with_extension(ext),
+        let target_path = self.get_target_path(provider, ext, &tags, &data)?;
+        let id = tags.info.as_ref().and_then(|info| info.id);
+        tags.lyrics = self.find_lyrics(&path, id)?;
+        self.write_cover(&target_path, &tags.image)?;
+        // The qmc music embeds its own tags, only the missing ones are filled by default.
+        let policy = match (self.command.tag_policy, provider.get_format()) {
+            (Some(policy), _) => policy,
+            (None, FileType::Qmc) => TagPolicy::FillMissing,
+            (None, _) => TagPolicy::Overwrite,
+        };
+        let options = TagOptions {
+            policy,
+            ..TagOptions::clone(&self.tag_options)
+        };
+        // The salvaged ncm and the qmc music may be clipped.
+        let lenient = report.is_some() || provider.get_format() == FileType::Qmc;
+        let buffer = match tag::apply(&data, &tags, &options) {
+            Ok(buffer) => buffer,
+            Err(err) if lenient && err.is_truncated() => {
+                self.state.warn(&format!(
+                    "{} has incomplete {} metadata, the audio is written without tags",
+                    provider.get_name(),
+                    ext,
+                ));
+                data
+            }
+            Err(err) => return Err(err.into()),
         };
+        // The target is opened after the music is tagged, so no empty file is left on error.
         let mut target = File::options()
             .create(true)
             .write(true)
             .truncate(true)
-            .open(target_path)?;
-        if provider.get_format() == FileType::Ncm {
-            let file = File::open(provider.get_path())?;
-            let mut dump = Ncmdump::from_reader(file)?;
-            let image = dump.get_image()?;
-            let info = dump.get_info()?;
-            if ext == "mp3" {
-                let buffer = Mp3Metadata::new(&info, &image, &data).inject_metadata(data)?;
-                target.write_all(&buffer)?;
-            } else if ext == "flac" {
-                let buffer = FlacMetadata::new(&info, &image, &data).inject_metadata(data)?;
-                target.write_all(&buffer)?;
-            }
-        }
+            .open(&target_path)?;
+        target.write_all(&buffer)?;
         Ok(())
     }
 
-    fn get_data<R, P>(&self, mut dump: R, provider: &P) -> Result<Vec<u8>>
+    /// Get the path of the output file, it's named by the name templates, or the name of
+    /// input file if none of the templates can be rendered. The name is sanitised, and
+    /// the directories of the rendered name are created.
+    ///
+    /// The path is claimed by the file, if it's claimed by another one, the name is
+    /// suffixed with the number like `name (2)`.
+    fn get_target_path<P>(
+        &self,
+        provider: &P,
+        ext: &str,
+        tags: &Tags,
+        data: &[u8],
+    ) -> Result<PathBuf>
     where
-        R: Read,
         P: DataProvider,
     {
-        let mut data = Vec::new();
-        let mut buffer = [0; 1024];
-        let progress = self.state.create_progress(provider)?;
-        while let Ok(size) = dump.read(&mut buffer) {
-            if size == 0 {
+        let path = provider.get_path();
+        let dir = match &self.command.output {
+            None => path.parent().unwrap_or(Path::new("")),
+            Some(p) => Path::new(p),
+        };
+        let (name, rendered) = match self.render_name(provider, tags, data) {
+            Some(name) => (name, true),
+            None => (PathBuf::from(path.file_stem().ok_or(Error::Path)?), false),
+        };
+        let target_path = self.claim_target_path(dir, &name, ext)?;
+        if let Some(parent) = target_path.parent().filter(|_| rendered) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Can't create the directory {}", parent.display()))?;
+        }
+        Ok(target_path)
+    }
+
+    /// Build the output path which isn't claimed by the other files, and claim it.
+    fn claim_target_path(&self, dir: &Path, name: &Path, ext: &str) -> Result<PathBuf> {
+        let mut target_paths = self.target_paths.lock().unwrap();
+        let mut target_path = self.sanitize_options.build_path(dir, name, ext)?;
+        let file_name = name.file_name().ok_or(Error::Path)?.to_string_lossy();
+        for number in 2.. {
+            let key = std::path::absolute(&target_path).unwrap_or_else(|_| target_path.clone());
+            if target_paths.insert(key) {
                 break;
             }
-            data.write_all(&buffer[..size])?;
-            self.state.inc(size as u64);
-            if let Some(p) = &progress {
-                p.inc(size as u64);
+            let name = name.with_file_name(format!("{} ({})", file_name, number));
+            let next_path = self.sanitize_options.build_path(dir, &name, ext)?;
+            // The suffix is truncated if the path is too long.
+            if next_path == target_path {
+                return Err(Error::PathLength.into());
+            }
+            target_path = next_path;
+        }
+        Ok(target_path)
+    }
+
+    /// Render the name of output file by the first template whose variables are all
+    /// present.
+    fn render_name<P>(&self, provider: &P, tags: &Tags, data: &[u8]) -> Option<PathBuf>
+    where
+        P: DataProvider,
+    {
+        let templates = &self.command.name_templates;
+        if templates.is_empty() {
+            return None;
+        }
+        let fields = self.get_name_fields(provider, tags, data);
+        let separator = self.command.name_separator.as_deref().unwrap_or(", ");
+        templates
+            .iter()
+            .find_map(|template| template.render(&fields, separator, &self.sanitize_options))
+    }
+
+    /// Get the fields to name the output file. They're mapped from the information of
+    /// the ncm file, or read from the embedded tags of the other music, whose missing
+    /// fields are guessed from the file name.
+    fn get_name_fields<P>(
+        &self,
+        provider: &P,
+        tags: &Tags,
+        data: &[u8],
+    ) -> Vec<(Field, Vec<String>)>
+    where
+        P: DataProvider,
+    {
+        let mapped = tags
+            .info
+            .as_ref()
+            .map(|info| tag::get_fields(info, &self.tag_options))
+            .unwrap_or_default();
+        if provider.get_format() == FileType::Ncm {
+            return mapped;
+        }
+        let mut fields = tag::read_fields(data).unwrap_or_default();
+        for (field, values) in mapped {
+            if !fields.iter().any(|(f, _)| *f == field) {
+                fields.push((field, values));
+            }
+        }
+        fields
+    }
+
+    /// Write the cover file into the directory of the music, it's only written once
+    /// for each directory, and the existing file isn't overwritten.
+    ///
+    /// The directory is marked after the cover is written, so it's retried by the next
+    /// music if it fails.
+    fn write_cover(&self, target_path: &Path, image: &[u8]) -> Result<()> {
+        let Some(name) = &self.command.cover_file else {
+            return Ok(());
+        };
+        if image.is_empty() {
+            return Ok(());
+        }
+        let dir = target_path.parent().unwrap_or(Path::new(""));
+        if self.cover_dirs.lock().unwrap().contains(dir) {
+            return Ok(());
+        }
+        let path = dir.join(name);
+        if !path.exists() {
+            let options = &self.tag_options.cover;
+            let cover = tag::to_jpeg(&options.process(image)?, options.quality)?;
+            // The cover may be written by another worker in the meantime.
+            match File::options().write(true).create_new(true).open(&path) {
+                // The incomplete cover is removed, so it's written again.
+                Ok(mut file) => file.write_all(&cover).inspect_err(|_| {
+                    let _ = fs::remove_file(&path);
+                }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+                Err(err) => Err(err),
             }
+            .with_context(|| format!("Can't write the cover {}", path.display()))?;
         }
-        if let Some(p) = &progress {
-            p.finish();
+        self.cover_dirs.lock().unwrap().insert(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Find the lrc lyrics of the music in the lyrics directory, or the directory of
+    /// the music if it isn't specified. The lyrics in the netease lyric cache are
+    /// appended if the song id is known, they're skipped with a warning if the cache
+    /// can't be loaded.
+    fn find_lyrics(&self, path: &Path, id: Option<u64>) -> Result<Vec<Lyrics>> {
+        let dir = match &self.command.lyrics_dir {
+            Some(dir) => dir.as_path(),
+            None => path.parent().unwrap_or(Path::new("")),
+        };
+        let stem = path.file_stem().ok_or(Error::Path)?.to_string_lossy();
+        let mut lyrics = lyrics::find_lyrics(&self.list_lyrics(dir)?, &stem)?;
+        if let (Some(dir), Some(id)) = (&self.command.netease_lyric_dir, id) {
+            match lyrics::load_netease_lyrics(dir, id) {
+                Ok(item) => lyrics.extend(item),
+                Err(err) => self.state.warn(&format!(
+                    "{:#}, the music is written without the netease lyrics",
+                    err
+                )),
+            }
         }
-        Ok(data)
+        Ok(lyrics)
+    }
+
+    /// List the lrc files in the directory, each directory is only listed once.
+    fn list_lyrics(&self, dir: &Path) -> Result<Arc<Vec<PathBuf>>> {
+        let dir = match dir.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => dir,
+        };
+        let mut lyric_files = self.lyric_files.lock().unwrap();
+        if let Some(files) = lyric_files.get(dir) {
+            return Ok(files.clone());
+        }
+        let files = Arc::new(lyrics::list_lyrics(dir)?);
+        lyric_files.insert(dir.to_path_buf(), files.clone());
+        Ok(files)
+    }
+
+    /// Get the tags of the ncm file, the missing metadata of the salvaged file is skipped,
+    /// and the information which can't be decoded is skipped with a warning.
+    fn get_ncm_tags(
+        &self,
+        dump: &mut Ncmdump<File>,
+        report: Option<&SalvageReport>,
+        name: &str,
+    ) -> Result<Tags> {
+        let Some(report) = report else {
+            return Ok(dump.get_tags()?);
+        };
+        let (info, comment) = match report.is_missing(Section::Info) {
+            true => (None, None),
+            false => match (dump.get_info(), dump.get_comment()) {
+                (Ok(info), Ok(comment)) => (Some(info), Some(comment)),
+                (info, comment) => {
+                    let err = info.as_ref().err().or(comment.as_ref().err());
+                    if let Some(err) = err {
+                        self.state.warn(&format!(
+                            "{} has invalid information, it's skipped: {}",
+                            name, err,
+                        ));
+                    }
+                    (info.ok(), comment.ok())
+                }
+            },
+        };
+        Ok(Tags {
+            info,
+            image: match report.is_missing(Section::Image) {
+                true => Vec::new(),
+                false => dump.get_image()?,
+            },
+            comment: comment.filter(|c| !c.is_empty()),
+            lyrics: Vec::new(),
+        })
+    }
+
+    /// Open the ncm file, it's salvaged leniently with the `--salvage` flag.
+    fn open_ncm<P>(
+        &self,
+        source: File,
+        provider: &P,
+    ) -> Result<(Ncmdump<File>, Option<SalvageReport>)>
+    where
+        P: DataProvider,
+    {
+        if !self.command.salvage {
+            return Ok((Ncmdump::from_reader(source)?, None));
+        }
+        let (dump, report) = Ncmdump::salvage(source)?;
+        let name = provider.get_name();
+        if let Some(section) = report.truncated {
+            self.state.warn(&format!(
+                "{} is truncated in the {} area, the missing metadata is skipped",
+                name, section,
+            ));
+        } else if let Some(missing) = report.missing_audio_length() {
+            // The bitrate is only an average, ignore the small difference.
+            if missing > report.expected_audio_length.unwrap_or(0) / 100 {
+                self.state.warn(&format!(
+                    "{} is likely missing about {} bytes of audio data",
+                    name, missing,
+                ));
+            }
+        }
+        Ok((dump, Some(report)))
     }
 
     fn start(&self) -> Result<()> {
@@ -120,7 +423,8 @@ impl Program {
         let state = self.state.clone();
         tasks.push(thread::spawn(move || {
             for path in items {
-                let provider = FileProvider::new(path).map_err(|_| Error::Path)?;
+                let provider = FileProvider::new(path.clone())
+                    .with_context(|| format!("Can't open {}", path.display()))?;
                 state.inc_length(provider.get_size());
                 tx.send(provider)?;
             }
@@ -132,7 +436,9 @@ impl Program {
             let state = self.clone();
             let task = thread::spawn(move || {
                 while let Ok(w) = rx.recv() {
-                    state.dump(&w)?;
+                    state
+                        .dump(&w)
+                        .with_context(|| format!("Can't convert {}", w.get_path().display()))?;
                 }
                 anyhow::Ok(())
             });
@@ -147,6 +453,9 @@ impl Program {
 
 fn main() -> Result<()> {
     let command = Command::parse();
+    if let Some(Subcommands::Inspect(inspect)) = &command.subcommand {
+        return inspect.run();
+    }
     command.invalid()?;
 
     let program = Program::new(command)?;
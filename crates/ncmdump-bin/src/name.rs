@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+
+use ncmdump::tag::template::{self, Segment};
+use ncmdump::tag::Field;
+
+use crate::sanitize::SanitizeOptions;
+
+/// The template of the output file name, like `{artist} - {title}` or
+/// `{album_artist}/{album}/{title}`, the `/` separates the directories.
+///
+/// The variables are the names of the tag fields, see [`template`] for the syntax of
+/// the indexes, fallbacks and lengths. The template can't be rendered if any of its
+/// variables is missing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct NameTemplate {
+    segments: Vec<Segment>,
+}
+
+impl NameTemplate {
+    /// Parse the template, the unknown variables are rejected.
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let segments = template::parse(source)?;
+        let variables = segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Variable(variable) => Some(variable),
+                Segment::Text(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if variables.is_empty() {
+            return Err(format!("no variable in {:?}", source));
+        }
+        let mut names = variables.iter().flat_map(|v| &v.alternatives);
+        if let Some((name, _)) = names.find(|(name, _)| Field::from_name(name).is_none()) {
+            return Err(format!("unknown variable `{}`", name));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Render the template into the relative path without extension, none if any of
+    /// the variables is missing.
+    ///
+    /// The lists are joined with the separator, and the path separators in the values
+    /// are replaced by the options. The empty, `.` and `..` components are skipped.
+    pub(crate) fn render(
+        &self,
+        fields: &[(Field, Vec<String>)],
+        separator: &str,
+        options: &SanitizeOptions,
+    ) -> Option<PathBuf> {
+        let mut output = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => output.push_str(text),
+                Segment::Variable(variable) => {
+                    let value = variable.render(|name, index| {
+                        let value = get_value(fields, Field::from_name(name)?, index, separator)?;
+                        Some(options.escape(&value))
+                    })?;
+                    output.push_str(value.trim());
+                }
+            }
+        }
+        let path = output
+            .split('/')
+            .map(str::trim)
+            .filter(|component| !matches!(*component, "" | "." | ".."))
+            .collect::<PathBuf>();
+        match path.as_os_str().is_empty() {
+            true => None,
+            false => Some(path),
+        }
+    }
+}
+
+/// Get the value of the field, the list is joined with the separator if it's not
+/// indexed. It's none if the value is missing or empty.
+fn get_value(
+    fields: &[(Field, Vec<String>)],
+    field: Field,
+    index: Option<usize>,
+    separator: &str,
+) -> Option<String> {
+    let (_, values) = fields.iter().find(|(f, _)| *f == field)?;
+    let value = match index {
+        Some(index) => values.get(index)?.to_owned(),
+        None => values.join(separator),
+    };
+    Some(value).filter(|value| !value.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn get_fields() -> Vec<(Field, Vec<String>)> {
+        vec![
+            (Field::Title, vec!["Golden Hour".into()]),
+            (Field::Album, vec!["AC/DC Live".into()]),
+            (Field::Artist, vec!["JVKE".into(), "Other".into()]),
+        ]
+    }
+
+    #[test]
+    fn test_render_name_ok() -> Result<(), String> {
+        let fields = get_fields();
+        let render = |source: &str| -> Result<Option<PathBuf>, String> {
+            let options = SanitizeOptions::default();
+            Ok(NameTemplate::parse(source)?.render(&fields, ", ", &options))
+        };
+        assert_eq!(
+            render("{artist} - {title}")?.as_deref(),
+            Some(Path::new("JVKE, Other - Golden Hour"))
+        );
+        assert_eq!(
+            render("{album_artist|artist[0]}/{album}/{title:6}")?.as_deref(),
+            Some(Path::new("JVKE/AC_DC Live/Golden"))
+        );
+        assert_eq!(
+            render("{{{subtitle|title}}}")?.as_deref(),
+            Some(Path::new("{Golden Hour}"))
+        );
+        assert_eq!(
+            render("../{title}/./")?.as_deref(),
+            Some(Path::new("Golden Hour"))
+        );
+        assert_eq!(render("{album_artist} - {title}")?, None);
+        assert_eq!(render("{artist[2]}")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_name_err() {
+        let sources = [
+            "{name}",
+            "{title",
+            "title}",
+            "{artist[x]}",
+            "{title:0}",
+            "{title|}",
+            "plain",
+        ];
+        for source in sources {
+            assert!(NameTemplate::parse(source).is_err(), "{}", source);
+        }
+    }
+}
@@ -3,6 +3,7 @@ use std::path::PathBuf;
 
 use anyhow::Result;
 use ncmdump::utils::FileType;
+use ncmdump::NcmInfo;
 
 use crate::errors::Error;
 
@@ -11,6 +12,36 @@ pub(crate) trait DataProvider {
     fn get_path(&self) -> PathBuf;
     fn get_format(&self) -> FileType;
     fn get_size(&self) -> u64;
+
+    /// Guess the title and artist from the file name, like `Artist - Title.qmcflac`.
+    fn get_name_info(&self) -> NcmInfo {
+        let path = self.get_path();
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        parse_name(&stem)
+    }
+}
+
+/// Parse the `Artist - Title` or `Title` file stem into the information.
+fn parse_name(stem: &str) -> NcmInfo {
+    let (artist, name) = match stem.split_once(" - ") {
+        Some((artist, name)) if !artist.trim().is_empty() => (Some(artist.trim()), name),
+        _ => (None, stem),
+    };
+    NcmInfo {
+        name: name.trim().to_string(),
+        id: None,
+        album: String::new(),
+        album_id: None,
+        artist: artist
+            .map(|artist| vec![(artist.to_string(), None)])
+            .unwrap_or_default(),
+        bitrate: None,
+        duration: None,
+        format: String::new(),
+        mv_id: None,
+        alias: None,
+        trans_names: None,
+    }
 }
 
 pub(crate) struct FileProvider {
@@ -62,3 +93,27 @@ impl FileProvider {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name_ok() {
+        let info = parse_name("JVKE - Golden Hour");
+        assert_eq!(info.name, "Golden Hour");
+        assert_eq!(info.artist, vec![("JVKE".to_string(), None)]);
+
+        let info = parse_name("A - B - C");
+        assert_eq!(info.name, "B - C");
+        assert_eq!(info.artist, vec![("A".to_string(), None)]);
+
+        let info = parse_name("Golden Hour");
+        assert_eq!(info.name, "Golden Hour");
+        assert!(info.artist.is_empty());
+
+        let info = parse_name(" - Golden Hour");
+        assert_eq!(info.name, "- Golden Hour");
+        assert!(info.artist.is_empty());
+    }
+}
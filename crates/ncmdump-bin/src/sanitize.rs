@@ -0,0 +1,284 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::errors::Error;
+
+/// The characters which are reserved by Windows, FAT and exFAT.
+const RESERVED_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+/// The full-width look-alikes of the reserved characters, in the same order.
+const FULLWIDTH_CHARS: [char; 9] = ['＜', '＞', '：', '＂', '／', '＼', '｜', '？', '＊'];
+/// The device names which are reserved by Windows, with or without an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The replacement of the reserved characters.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Replacement {
+    /// Replace the reserved characters with the text
+    Text(String),
+    /// Replace the reserved characters with their full-width look-alikes, like `：`
+    FullWidth,
+}
+
+impl Default for Replacement {
+    fn default() -> Self {
+        Self::Text("_".to_string())
+    }
+}
+
+/// Parse the replacement, `fullwidth` or the text without reserved characters.
+pub(crate) fn parse_replacement(value: &str) -> Result<Replacement, String> {
+    if value == "fullwidth" {
+        return Ok(Replacement::FullWidth);
+    }
+    match value
+        .chars()
+        .any(|c| RESERVED_CHARS.contains(&c) || c.is_control())
+    {
+        true => Err(format!(
+            "the replacement `{}` has reserved characters",
+            value
+        )),
+        false => Ok(Replacement::Text(value.to_string())),
+    }
+}
+
+/// The options of sanitising the output file names, the names are kept valid on the
+/// Windows, FAT32 and exFAT file systems.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SanitizeOptions {
+    /// The replacement of the reserved and control characters, default is `_`
+    pub(crate) replacement: Replacement,
+    /// Transliterate the names into ascii for the old devices, default is false
+    pub(crate) ascii: bool,
+    /// The maximum bytes of each path component, default is 255
+    pub(crate) max_name_bytes: usize,
+    /// The maximum bytes of the absolute path, default is 4095
+    pub(crate) max_path_bytes: usize,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self {
+            replacement: Replacement::default(),
+            ascii: false,
+            max_name_bytes: 255,
+            max_path_bytes: 4095,
+        }
+    }
+}
+
+impl SanitizeOptions {
+    /// Get the replacement of the reserved character, the full-width look-alikes are
+    /// not ascii, so `_` is used instead when the names are transliterated.
+    fn replace(&self, c: char) -> String {
+        let position = RESERVED_CHARS.iter().position(|r| *r == c);
+        match (&self.replacement, position) {
+            (Replacement::FullWidth, Some(position)) if !self.ascii => {
+                FULLWIDTH_CHARS[position].to_string()
+            }
+            (Replacement::Text(text), _) => text.to_owned(),
+            _ => "_".to_string(),
+        }
+    }
+
+    /// Replace the path separators in the value, so it's kept in one component.
+    pub(crate) fn escape(&self, value: &str) -> String {
+        let mut output = String::with_capacity(value.len());
+        for c in value.chars() {
+            match c {
+                '/' | '\\' => output.push_str(&self.replace(c)),
+                c => output.push(c),
+            }
+        }
+        output
+    }
+
+    /// Sanitise the path component, the reserved and control characters are replaced,
+    /// the trailing dots and spaces are removed, and the reserved names are suffixed
+    /// with `_`.
+    pub(crate) fn sanitize(&self, name: &str) -> String {
+        let name = match self.ascii {
+            true => deunicode::deunicode(name),
+            false => name.to_string(),
+        };
+        let mut output = String::with_capacity(name.len());
+        for c in name.chars() {
+            match c {
+                c if RESERVED_CHARS.contains(&c) || c.is_control() => {
+                    output.push_str(&self.replace(c))
+                }
+                c => output.push(c),
+            }
+        }
+        let output = output.trim_start().trim_end_matches(['.', ' ']);
+        let mut output = match output.is_empty() {
+            true => "_".to_string(),
+            false => output.to_string(),
+        };
+        if let Some(end) = find_device(&output) {
+            output.insert(end, '_');
+        }
+        output
+    }
+
+    /// Build the output path in the directory, the components of the relative name are
+    /// sanitised and the extension is appended to the last one.
+    ///
+    /// The components are truncated to the maximum bytes, and the file stem is
+    /// shortened to fit the maximum bytes of the absolute path.
+    pub(crate) fn build_path(&self, dir: &Path, name: &Path, ext: &str) -> Result<PathBuf, Error> {
+        let mut components = name
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .map(|name| self.sanitize(&name))
+            .collect::<Vec<_>>();
+        let stem = components.pop().ok_or(Error::Path)?;
+        let mut path = dir.to_path_buf();
+        for component in &components {
+            path.push(fit(component, self.max_name_bytes)?);
+        }
+
+        let suffix = format!(".{}", ext);
+        let absolute = std::path::absolute(&path).unwrap_or_else(|_| path.clone());
+        // The separator before the file name is counted.
+        let used = absolute.as_os_str().len() + 1 + suffix.len();
+        let limit = self
+            .max_name_bytes
+            .saturating_sub(suffix.len())
+            .min(self.max_path_bytes.saturating_sub(used));
+        path.push(format!("{}{}", fit(&stem, limit)?, suffix));
+        Ok(path)
+    }
+}
+
+/// Fit the sanitised name into the maximum bytes, the name is fixed again if the
+/// truncation leaves the trailing dots and spaces or a reserved name.
+fn fit(name: &str, max: usize) -> Result<String, Error> {
+    let mut output = truncate(name, max).trim_end_matches(['.', ' ']).to_string();
+    if let Some(end) = find_device(&output) {
+        output.insert(end, '_');
+        if output.len() > max {
+            match end < max {
+                true => {
+                    output = truncate(&output, max)
+                        .trim_end_matches(['.', ' '])
+                        .to_string()
+                }
+                // The `_` replaces the last character of the device name.
+                false => {
+                    output.remove(end - 1);
+                }
+            }
+        }
+    }
+    match output.is_empty() {
+        true => Err(Error::PathLength),
+        false => Ok(output),
+    }
+}
+
+/// Find the reserved device name at the beginning of the name, the end of the device
+/// name is returned.
+fn find_device(name: &str) -> Option<usize> {
+    let device = name.split('.').next().unwrap_or_default().trim_end();
+    RESERVED_NAMES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(device))
+        .then_some(device.len())
+}
+
+/// Truncate the name to the maximum bytes at the character boundary.
+fn truncate(name: &str, max: usize) -> &str {
+    let mut end = name.len().min(max);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_ok() {
+        let options = SanitizeOptions::default();
+        assert_eq!(options.sanitize("AC/DC: Live?"), "AC_DC_ Live_");
+        assert_eq!(options.sanitize(" Title... "), "Title");
+        assert_eq!(options.sanitize("a\tb"), "a_b");
+        assert_eq!(options.sanitize("..."), "_");
+        assert_eq!(options.sanitize("con"), "con_");
+        assert_eq!(options.sanitize("LPT1.live"), "LPT1_.live");
+        assert_eq!(options.sanitize("Console"), "Console");
+        assert_eq!(options.escape("AC/DC\\"), "AC_DC_");
+
+        let options = SanitizeOptions {
+            replacement: Replacement::FullWidth,
+            ..Default::default()
+        };
+        assert_eq!(options.sanitize("What? <3"), "What？ ＜3");
+        assert_eq!(options.escape("AC/DC"), "AC／DC");
+
+        let options = SanitizeOptions {
+            replacement: Replacement::FullWidth,
+            ascii: true,
+            ..Default::default()
+        };
+        assert_eq!(options.sanitize("寒鸦少年：Live"), "Han Ya Shao Nian _Live");
+        assert_eq!(options.sanitize("Café"), "Cafe");
+    }
+
+    #[test]
+    fn test_parse_replacement_ok() {
+        assert_eq!(parse_replacement("fullwidth"), Ok(Replacement::FullWidth));
+        assert_eq!(
+            parse_replacement(" - "),
+            Ok(Replacement::Text(" - ".to_string()))
+        );
+        assert!(parse_replacement(":").is_err());
+    }
+
+    #[test]
+    fn test_build_path_ok() -> Result<(), Error> {
+        let options = SanitizeOptions {
+            max_name_bytes: 16,
+            ..Default::default()
+        };
+        let path = options.build_path(
+            Path::new("output"),
+            Path::new("华晨宇/寒鸦少年 (电视剧主题曲)"),
+            "flac",
+        )?;
+        // The multibyte characters are kept complete.
+        assert_eq!(path, Path::new("output/华晨宇/寒鸦少.flac"));
+        let path = options.build_path(Path::new(""), Path::new("../a.b"), "mp3")?;
+        assert_eq!(path, Path::new("a.b.mp3"));
+
+        // The reserved names left by the truncation are fixed within the maximum bytes.
+        let options = SanitizeOptions {
+            max_name_bytes: 9,
+            ..Default::default()
+        };
+        let path =
+            options.build_path(Path::new(""), Path::new("Aux      Live/COM1 Live"), "flac")?;
+        assert_eq!(path, Path::new("Aux_/COM_.flac"));
+        let path = options.build_path(Path::new(""), Path::new("Con.  Live"), "mp3")?;
+        assert_eq!(path, Path::new("Con_.mp3"));
+
+        let dir = std::path::absolute("output").map_err(Error::Io)?;
+        let options = SanitizeOptions {
+            max_path_bytes: dir.as_os_str().len() + 10,
+            ..Default::default()
+        };
+        let path = options.build_path(&dir, Path::new("Golden Hour"), "flac")?;
+        assert_eq!(path, dir.join("Gold.flac"));
+        let path = options.build_path(&dir, Path::new("Long/Golden Hour"), "flac");
+        assert!(matches!(path, Err(Error::PathLength)));
+        Ok(())
+    }
+}
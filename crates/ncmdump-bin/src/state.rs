@@ -1,5 +1,8 @@
+use std::cell::Cell;
+
 use anyhow::Result;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use ncmdump::Progress;
 
 use crate::command::Command;
 use crate::errors::Error;
@@ -17,27 +20,62 @@ pub(crate) struct State {
 
 impl State {
     /// Create a new progress.
-    pub(crate) fn create_progress<P>(&self, provider: &P) -> Result<Option<ProgressBar>>
+    pub(crate) fn create_progress<P>(&self, provider: &P) -> Result<FileProgress>
     where
         P: DataProvider,
     {
-        if !self.verbose {
-            return Ok(None);
-        }
-        let style = ProgressStyle::with_template(SINGLE_PSTYPE)?;
-        let progress = self
-            .group
-            .insert_from_back(1, ProgressBar::new(provider.get_size()).with_style(style));
-        progress.set_message(provider.get_name());
-        Ok(Some(progress))
+        let single = match self.verbose {
+            true => {
+                let style = ProgressStyle::with_template(SINGLE_PSTYPE)?;
+                let progress = self
+                    .group
+                    .insert_from_back(1, ProgressBar::new(provider.get_size()).with_style(style));
+                progress.set_message(provider.get_name());
+                Some(progress)
+            }
+            false => None,
+        };
+        Ok(FileProgress {
+            total: self.total.clone(),
+            single,
+            position: Cell::new(0),
+        })
+    }
+
+    /// Print the warning message above the progress bars.
+    pub(crate) fn warn(&self, message: &str) {
+        self.group.suspend(|| eprintln!("Warning: {}", message));
     }
 
     pub(crate) fn inc_length(&self, num: u64) {
         self.total.inc_length(num);
     }
+}
+
+/// The progress of a single file, it also moves the total progress.
+pub(crate) struct FileProgress {
+    total: ProgressBar,
+    single: Option<ProgressBar>,
+    position: Cell<u64>,
+}
+
+impl FileProgress {
+    /// Finish the progress of the file.
+    pub(crate) fn finish(&self) {
+        if let Some(p) = &self.single {
+            p.finish();
+        }
+    }
+}
 
-    pub(crate) fn inc(&self, num: u64) {
-        self.total.inc(num);
+impl Progress for FileProgress {
+    fn update(&self, done: u64, total: u64) {
+        let delta = done.saturating_sub(self.position.replace(done));
+        self.total.inc(delta);
+        if let Some(p) = &self.single {
+            p.set_length(total);
+            p.set_position(done);
+        }
     }
 }
 
@@ -0,0 +1,22 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Result;
+
+/// Create an empty directory for the test.
+pub fn create_dir(name: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("ncmdump-{}-{}", name, std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Create the command of the program with the output directory.
+pub fn ncmdump(output: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_ncmdump"));
+    command.arg("-o").arg(output);
+    command
+}
@@ -0,0 +1,43 @@
+use std::fs;
+
+use anyhow::Result;
+
+use crate::common::{create_dir, ncmdump};
+
+mod common;
+
+#[test]
+fn test_write_cover_file_ok() -> Result<()> {
+    let dir = create_dir("cover")?;
+    let output = dir.join("output");
+    fs::create_dir_all(&output)?;
+    let inputs = ["first.ncm", "second.ncm"].map(|name| dir.join(name));
+    for input in &inputs {
+        fs::copy("../ncmdump/res/test.ncm", input)?;
+    }
+    let status = ncmdump(&output)
+        .args(&inputs)
+        .args(["--cover-file", "folder.jpg", "--cover-max-size", "64"])
+        .args(["--worker", "2"])
+        .status()?;
+    assert!(status.success());
+
+    let cover = fs::read(output.join("folder.jpg"))?;
+    assert_eq!(cover[..3], [0xFF, 0xD8, 0xFF]);
+    let mut entries = fs::read_dir(&output)?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+    assert_eq!(entries, ["first.flac", "folder.jpg", "second.flac"]);
+
+    // The existing cover file isn't overwritten.
+    fs::write(output.join("folder.jpg"), b"keep")?;
+    let status = ncmdump(&output)
+        .arg(&inputs[0])
+        .args(["--cover-file", "folder.jpg"])
+        .status()?;
+    assert!(status.success());
+    assert_eq!(fs::read(output.join("folder.jpg"))?, b"keep");
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::fs;
+
+use anyhow::Result;
+
+use crate::common::{create_dir, ncmdump};
+
+mod common;
+
+#[test]
+fn test_dump_netease_lyrics_ok() -> Result<()> {
+    // The lyric cache of the music is invalid, so it's skipped with a warning.
+    let dir = create_dir("dump-lyrics")?;
+    let lyric_dir = dir.join("lyric");
+    fs::create_dir_all(&lyric_dir)?;
+    fs::write(lyric_dir.join("1305366556"), "invalid")?;
+    let input = dir.join("test.ncm");
+    fs::copy("../ncmdump/res/test.ncm", &input)?;
+
+    let output = ncmdump(&dir)
+        .arg(&input)
+        .arg("--netease-lyric-dir")
+        .arg(&lyric_dir)
+        .output()?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Warning: Can't parse the lyrics"));
+    assert!(dir.join("test.flac").is_file());
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_dump_cover_err() -> Result<()> {
+    // The cover file can't be written, so no output file is left.
+    let dir = create_dir("dump-cover")?;
+    let input = dir.join("test.ncm");
+    fs::copy("../ncmdump/res/test.ncm", &input)?;
+
+    let output = ncmdump(&dir)
+        .arg(&input)
+        .args(["--cover-file", "missing/folder.jpg"])
+        .output()?;
+    assert!(!output.status.success());
+    assert!(!dir.join("test.flac").exists());
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_dump_salvage_invalid_info_ok() -> Result<()> {
+    // The information is corrupted but not truncated, it's skipped with a warning.
+    let dir = create_dir("dump-salvage")?;
+    let mut data = fs::read("../ncmdump/res/test.ncm")?;
+    data[200] = b'!' ^ 0x63;
+    let input = dir.join("test.ncm");
+    fs::write(&input, &data)?;
+
+    let output = ncmdump(&dir).arg(&input).output()?;
+    assert!(!output.status.success());
+    let output = ncmdump(&dir).arg(&input).arg("--salvage").output()?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("has invalid information"));
+    assert!(dir.join("test.flac").is_file());
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_dump_lyrics_dir_err() -> Result<()> {
+    // The lyrics directory can't be read, so it fails before any conversion.
+    let dir = create_dir("dump-lyrics-dir")?;
+    let input = dir.join("test.ncm");
+    fs::copy("../ncmdump/res/test.ncm", &input)?;
+
+    let output = ncmdump(&dir)
+        .arg(&input)
+        .arg("--lyrics-dir")
+        .arg(dir.join("missing"))
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Can't read the lyrics directory"));
+    assert!(!dir.join("test.flac").exists());
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
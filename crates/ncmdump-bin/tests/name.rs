@@ -0,0 +1,76 @@
+use std::fs;
+
+use anyhow::Result;
+
+use crate::common::{create_dir, ncmdump};
+
+mod common;
+
+#[test]
+fn test_name_template_ok() -> Result<()> {
+    let dir = create_dir("name")?;
+    let output = dir.join("output");
+    let input = dir.join("1305366556.ncm");
+    fs::copy("../ncmdump/res/test.ncm", &input)?;
+    let status = ncmdump(&output)
+        .arg(&input)
+        .args(["--name-template", "{subtitle:4}/{album_artist} - {title}"])
+        .status()?;
+    assert!(status.success());
+    assert!(output
+        .join("电视剧《")
+        .join("华晨宇 - 寒鸦少年.flac")
+        .is_file());
+
+    // The name of input file is kept if none of the templates can be rendered.
+    let status = ncmdump(&output)
+        .arg(&input)
+        .args([
+            "--name-template",
+            "{album_artist} - {title}[{netease_album_id[1]}]",
+        ])
+        .status()?;
+    assert!(status.success());
+    assert!(output.join("1305366556.flac").is_file());
+
+    // The reserved characters are replaced, and the name is transliterated.
+    let status = ncmdump(&output)
+        .arg(&input)
+        .args([
+            "--name-template",
+            "{title}: {album_artist}?",
+            "--ascii-names",
+        ])
+        .status()?;
+    assert!(status.success());
+    assert!(output
+        .join("Han Ya Shao Nian _ Hua Chen Yu _.flac")
+        .is_file());
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_name_conflict_ok() -> Result<()> {
+    let dir = create_dir("name-conflict")?;
+    let output = dir.join("output");
+    let inputs = ["first.ncm", "second.ncm", "third.ncm"].map(|name| dir.join(name));
+    for input in &inputs {
+        fs::copy("../ncmdump/res/test.ncm", input)?;
+    }
+    let status = ncmdump(&output)
+        .args(&inputs)
+        .args(["--name-template", "{title}", "--worker", "3"])
+        .status()?;
+    assert!(status.success());
+    let mut entries = fs::read_dir(&output)?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+    assert_eq!(
+        entries,
+        ["寒鸦少年 (2).flac", "寒鸦少年 (3).flac", "寒鸦少年.flac"]
+    );
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
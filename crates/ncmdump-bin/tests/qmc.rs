@@ -0,0 +1,72 @@
+use std::fs;
+
+use anyhow::Result;
+use ncmdump::QmcCipher;
+
+use crate::common::{create_dir, ncmdump};
+
+mod common;
+
+/// Build a flac music with the comments, the audio frames are filled with the marker.
+fn build_flac(comments: &[&str], audio: &[u8]) -> Vec<u8> {
+    let mut data = b"fLaC".to_vec();
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x22]);
+    data.extend_from_slice(&[0x10, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    data.extend_from_slice(&[0x0A, 0xC4, 0x42, 0xF0, 0x00, 0x00, 0x00, 0x00]);
+    data.extend_from_slice(&[0; 16]);
+
+    let mut block = Vec::new();
+    block.extend_from_slice(&4u32.to_le_bytes());
+    block.extend_from_slice(b"test");
+    block.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        block.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        block.extend_from_slice(comment.as_bytes());
+    }
+    data.push(0x84);
+    data.extend_from_slice(&(block.len() as u32).to_be_bytes()[1..]);
+    data.extend_from_slice(&block);
+    data.extend_from_slice(audio);
+    data
+}
+
+fn contains(data: &[u8], value: &[u8]) -> bool {
+    data.windows(value.len()).any(|window| window == value)
+}
+
+#[test]
+fn test_convert_qmc_ok() -> Result<()> {
+    let dir = create_dir("qmc")?;
+    let audio = [0xFF, 0xF8, 0x69, 0x08].repeat(1024);
+    let mut data = build_flac(&["TITLE=Embedded Title", "ENCODER=test"], &audio);
+    QmcCipher::decrypt(0, &mut data);
+    let input = dir.join("JVKE - Golden Hour.qmcflac");
+    fs::write(&input, &data)?;
+
+    assert!(ncmdump(&dir).arg(&input).status()?.success());
+    let output = fs::read(dir.join("JVKE - Golden Hour.flac"))?;
+    assert_eq!(output[..4], *b"fLaC");
+    assert!(output.len() > data.len());
+    assert!(output.ends_with(&audio));
+    assert!(contains(&output, b"TITLE=Embedded Title"));
+    assert!(contains(&output, b"ENCODER=test"));
+    assert!(contains(&output, b"ARTIST=JVKE"));
+    assert!(!contains(&output, b"Golden Hour"));
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_convert_clipped_qmc_ok() -> Result<()> {
+    // The flac metadata of the clipped file is incomplete, it's written without tags.
+    let dir = create_dir("clipped-qmc")?;
+    let input = dir.join("test.qmcflac");
+    fs::copy("../ncmdump/res/test.qmcflac", &input)?;
+
+    assert!(ncmdump(&dir).arg(&input).status()?.success());
+    let output = fs::read(dir.join("test.flac"))?;
+    assert_eq!(output.len(), 4096);
+    assert_eq!(output[..4], *b"fLaC");
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
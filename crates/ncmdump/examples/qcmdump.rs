@@ -11,7 +11,7 @@ use ncmdump::QmcDump;
 fn main() -> Result<(), Error> {
     let file = File::open("res/test.qmcflac")?;
     let mut qmc = QmcDump::from_reader(file).expect("Can't create dump");
-    let data = qmc.get_data()?;
+    let data = qmc.get_data().expect("Can't get data");
 
     let mut target = File::options()
         .create(true)
@@ -0,0 +1,270 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::error::{Errors, Result};
+use crate::progress::{self, CancelToken};
+use crate::tag::{self, Container, Dump, TagOptions, Tags};
+use crate::utils::FileType;
+#[cfg(feature = "qmcdump")]
+use crate::QmcDump;
+use crate::{Limits, NcmInfo, Ncmdump};
+
+/// The options of [`convert`].
+#[derive(Clone, Debug)]
+pub struct ConvertOptions {
+    /// Write the tags into the music, default is true
+    pub tag: bool,
+    /// The options of writing tags
+    pub tag_options: TagOptions,
+    /// The size limits of the areas in the ncm file
+    pub limits: Limits,
+    /// The token to cancel the conversion
+    pub cancel: CancelToken,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            tag: true,
+            tag_options: TagOptions::default(),
+            limits: Limits::default(),
+            cancel: CancelToken::new(),
+        }
+    }
+}
+
+/// The report of [`convert`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConvertReport {
+    /// The detected format of the encrypted file
+    pub format: FileType,
+    /// The sniffed container format of the music
+    pub container: Container,
+    /// The information of music, none if the file doesn't contain it
+    pub info: Option<NcmInfo>,
+    /// The length of the encrypted input
+    pub input_length: u64,
+    /// The length of the decrypted music, without the tags written
+    pub audio_length: u64,
+    /// The length of the output
+    pub output_length: u64,
+}
+
+impl ConvertReport {
+    /// The file extension of the output.
+    pub fn extension(&self) -> &'static str {
+        self.container.extension()
+    }
+}
+
+/// Convert the encrypted file into a tagged music in one call.
+///
+/// The format of input is detected from the current position, the music is decrypted,
+/// its container is sniffed, then it's tagged and written into the output. The offsets
+/// of the ncm file are relative to the current position, so it can be embedded in
+/// another stream.
+///
+/// # Example
+///
+/// ```rust
+/// use std::fs::File;
+///
+/// use anyhow::Result;
+/// use ncmdump::{convert, ConvertOptions};
+///
+/// fn main() -> Result<()> {
+///     let input = File::open("res/test.ncm")?;
+///     let mut output = Vec::new();
+///     let report = convert(input, &mut output, &ConvertOptions::default())?;
+///     assert_eq!(report.extension(), "flac");
+///     assert_eq!(report.output_length, output.len() as u64);
+///     Ok(())
+/// }
+/// ```
+pub fn convert<R, W>(
+    mut input: R,
+    output: &mut W,
+    options: &ConvertOptions,
+) -> Result<ConvertReport>
+where
+    R: Read + Seek,
+    W: Write + ?Sized,
+{
+    let start = input.stream_position()?;
+    let input_length = input.seek(SeekFrom::End(0))?.saturating_sub(start);
+    input.seek(SeekFrom::Start(start))?;
+    let format = FileType::parse(&mut input)?;
+    input.seek(SeekFrom::Start(start))?;
+
+    let (data, tags) = match format {
+        FileType::Ncm => {
+            let input = Shifted {
+                inner: input,
+                start,
+            };
+            let mut dump = Ncmdump::from_reader_with_limits(input, options.limits)?;
+            decrypt(&mut dump, options)?
+        }
+        #[cfg(feature = "qmcdump")]
+        FileType::Qmc => decrypt(&mut QmcDump::from_reader(input)?, options)?,
+        FileType::Other => return Err(Errors::InvalidFileType),
+    };
+    let container = Container::sniff(&data).ok_or(Errors::InvalidFileType)?;
+    let audio_length = data.len() as u64;
+    let buffer = match options.tag {
+        true => tag::apply(&data, &tags, &options.tag_options)?,
+        false => data,
+    };
+    output.write_all(&buffer)?;
+    Ok(ConvertReport {
+        format,
+        container,
+        info: tags.info,
+        input_length,
+        audio_length,
+        output_length: buffer.len() as u64,
+    })
+}
+
+/// The reader whose positions are relative to the start offset of the inner reader.
+struct Shifted<R> {
+    inner: R,
+    start: u64,
+}
+
+impl<R> Read for Shifted<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R> Seek for Shifted<R>
+where
+    R: Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(p) => SeekFrom::Start(self.start.checked_add(p).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position")
+            })?),
+            pos => pos,
+        };
+        let position = self.inner.seek(pos)?;
+        position
+            .checked_sub(self.start)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start"))
+    }
+}
+
+/// Decrypt the music and get the tags of the dumper, the tags are read even if they
+/// aren't written, so the information is reported.
+fn decrypt<D>(dump: &mut D, options: &ConvertOptions) -> Result<(Vec<u8>, Tags)>
+where
+    D: Dump,
+{
+    let mut data = Vec::new();
+    progress::copy(dump, &mut data, 0, 0, &|_, _| {}, &options.cancel)?;
+    Ok((data, dump.get_tags()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Cursor;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn test_convert_ncm_ok() -> Result<()> {
+        let input = File::open("res/test.ncm")?;
+        let mut output = Vec::new();
+        let report = convert(input, &mut output, &ConvertOptions::default())?;
+        assert_eq!(report.format, FileType::Ncm);
+        assert_eq!(report.container, Container::Flac);
+        assert_eq!(report.extension(), "flac");
+        assert_eq!(report.input_length, 101122);
+        assert_eq!(report.audio_length, 61440);
+        assert_eq!(report.output_length, output.len() as u64);
+        assert!(report.info.is_some());
+        assert_eq!(output[..4], *b"fLaC");
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_from_position_ok() -> Result<()> {
+        let mut data = vec![0; 100];
+        data.extend(std::fs::read("res/test.ncm")?);
+        let mut input = Cursor::new(data);
+        input.seek(SeekFrom::Start(100))?;
+        let mut output = Vec::new();
+        let report = convert(input, &mut output, &ConvertOptions::default())?;
+        assert_eq!(report.format, FileType::Ncm);
+        assert_eq!(report.input_length, 101122);
+        assert_eq!(report.audio_length, 61440);
+        assert_eq!(output[..4], *b"fLaC");
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_without_tag_ok() -> Result<()> {
+        let input = File::open("res/test.ncm")?;
+        let mut output = Vec::new();
+        let options = ConvertOptions {
+            tag: false,
+            ..Default::default()
+        };
+        let report = convert(input, &mut output, &options)?;
+        assert_eq!(
+            report.info.map(|info| info.name).as_deref(),
+            Some("寒鸦少年")
+        );
+        assert_eq!(report.output_length, 61440);
+        assert_eq!(
+            output,
+            Ncmdump::from_reader(File::open("res/test.ncm")?)?.get_data()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_qmc_ok() -> Result<()> {
+        // The test file is clipped, its flac metadata is incomplete.
+        let input = File::open("res/test.qmcflac")?;
+        let mut output = Vec::new();
+        let result = convert(input, &mut output, &ConvertOptions::default());
+        assert!(result.is_err_and(|err| err.is_truncated()));
+
+        let input = File::open("res/test.qmcflac")?;
+        let options = ConvertOptions {
+            tag: false,
+            ..Default::default()
+        };
+        let report = convert(input, &mut output, &options)?;
+        assert_eq!(report.format, FileType::Qmc);
+        assert_eq!(report.container, Container::Flac);
+        assert_eq!(report.info, None);
+        assert_eq!(report.input_length, 4096);
+        assert_eq!(report.output_length, 4096);
+        assert_eq!(output[..4], *b"fLaC");
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_err() {
+        let mut output = Vec::new();
+        let input = Cursor::new(b"RIFF....WAVE".to_vec());
+        let result = convert(input, &mut output, &ConvertOptions::default());
+        assert!(matches!(result, Err(Errors::InvalidFileType)));
+
+        let options = ConvertOptions::default();
+        options.cancel.cancel();
+        let input = File::open("res/test.ncm").unwrap();
+        let result = convert(input, &mut output, &options);
+        assert!(matches!(result, Err(Errors::Cancelled)));
+        assert!(output.is_empty());
+    }
+}
@@ -1,8 +1,46 @@
+use alloc::string::String;
+use core::fmt::{Display, Formatter};
+#[cfg(feature = "std")]
 use std::io;
 
 use thiserror::Error;
 
-pub(crate) type Result<T> = std::result::Result<T, Errors>;
+#[cfg(any(feature = "std", feature = "ncmdump"))]
+pub(crate) type Result<T> = core::result::Result<T, Errors>;
+
+/// The area of the file, in the order of the file.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Section {
+    /// The magic header and the version bytes
+    Magic,
+    /// The key area and its length
+    Key,
+    /// The information area and its length
+    Info,
+    /// The checksum and the gap byte
+    Checksum,
+    /// The cover frame and its length
+    Cover,
+    /// The image area and its length
+    Image,
+    /// The audio data
+    Audio,
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            Section::Magic => "magic",
+            Section::Key => "key",
+            Section::Info => "info",
+            Section::Checksum => "checksum",
+            Section::Cover => "cover frame",
+            Section::Image => "image",
+            Section::Audio => "audio",
+        };
+        f.write_str(name)
+    }
+}
 
 /// The error type for ncmdump.
 #[derive(Debug, Error)]
@@ -23,13 +61,69 @@ pub enum Errors {
     #[error("Invalid image area length")]
     InvalidImageLength,
 
+    /// The checksum of the file header is mismatched
+    #[error("Checksum mismatch: expected {expected:#010x}, got {actual:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    /// The area is larger than the limit
+    #[error("The {section} area is too large: {length} bytes, the limit is {limit} bytes")]
+    LimitExceeded {
+        section: Section,
+        length: u64,
+        limit: u64,
+    },
+
     /// Can't decode information of this file
     #[error("Can't decode information")]
     InfoDecodeError,
 
-    /// Can't decrypt data
-    #[error("Can't decrypt")]
-    DecryptError,
+    /// The field of information is present but invalid
+    #[error("Invalid field `{field}`: {reason}")]
+    InvalidField { field: String, reason: String },
+
+    /// Can't decrypt data, the key is wrong or the area is modified
+    #[error("Can't decrypt the {section} area at offset {offset}")]
+    DecryptError { section: Section, offset: u64 },
+
+    /// The data ends before the area is complete
+    #[error("Unexpected end of data in the {section} area at offset {offset}")]
+    Truncated { section: Section, offset: u64 },
+
+    /// Can't read the area of file
+    #[cfg(feature = "std")]
+    #[error("Can't read the {section} area at offset {offset}")]
+    Read {
+        section: Section,
+        offset: u64,
+        #[source]
+        source: io::Error,
+    },
+
+    /// Can't write the tag into the music
+    #[cfg(feature = "tag")]
+    #[error("Can't write the {container} tag")]
+    Tag {
+        container: crate::tag::Container,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// Can't decode or encode the cover image
+    #[cfg(feature = "tag")]
+    #[error("Can't process the cover image")]
+    Cover {
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// The template or key of the tag map is invalid
+    #[cfg(feature = "tag")]
+    #[error("Invalid tag map `{key}`: {reason}")]
+    InvalidTagMap { key: String, reason: String },
+
+    /// The conversion is cancelled by the token
+    #[error("The conversion is cancelled")]
+    Cancelled,
 
     /// Unknown error
     #[error("Unknown error")]
@@ -40,12 +134,44 @@ pub enum Errors {
     Decode,
 
     /// IO error
-    #[error("IO Error: {0}")]
-    IO(String),
+    #[cfg(feature = "std")]
+    #[error("IO error")]
+    IO(#[from] io::Error),
 }
 
-impl From<io::Error> for Errors {
-    fn from(value: io::Error) -> Self {
-        Self::IO(value.to_string())
+impl Errors {
+    /// Get the kind of the underlying io error, if it's caused by io.
+    #[cfg(feature = "std")]
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            Errors::Read { source, .. } | Errors::IO(source) => Some(source.kind()),
+            _ => None,
+        }
+    }
+
+    /// Check if the error is caused by an unexpected end of file.
+    pub fn is_truncated(&self) -> bool {
+        #[cfg(feature = "std")]
+        if self.io_kind() == Some(io::ErrorKind::UnexpectedEof) {
+            return true;
+        }
+        matches!(self, Errors::Truncated { .. })
+    }
+
+    /// Get the section where the error occurs, if it's known.
+    pub fn section(&self) -> Option<Section> {
+        match self {
+            Errors::LimitExceeded { section, .. }
+            | Errors::DecryptError { section, .. }
+            | Errors::Truncated { section, .. } => Some(*section),
+            #[cfg(feature = "std")]
+            Errors::Read { section, .. } => Some(*section),
+            _ => None,
+        }
+    }
+
+    /// Check if the error is caused by a wrong key, the file is usually modified.
+    pub fn is_wrong_key(&self) -> bool {
+        matches!(self, Errors::DecryptError { .. })
     }
 }
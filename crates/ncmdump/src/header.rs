@@ -0,0 +1,543 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use aes::Aes128;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use cipher::block_padding::{Pkcs7, UnpadError};
+use cipher::{BlockDecryptMut, KeyInit};
+use serde::Serialize;
+
+use crate::error::{Errors, Result, Section};
+use crate::ncmdump::{NcmInfo, RawNcmInfo};
+
+const HEADER_KEY: [u8; 16] = [
+    0x68, 0x7A, 0x48, 0x52, 0x41, 0x6D, 0x73, 0x6F, 0x35, 0x6B, 0x49, 0x6E, 0x62, 0x61, 0x78, 0x57,
+];
+
+const INFO_KEY: [u8; 16] = [
+    0x23, 0x31, 0x34, 0x6C, 0x6A, 0x6B, 0x5F, 0x21, 0x5C, 0x5D, 0x26, 0x30, 0x55, 0x3C, 0x27, 0x28,
+];
+
+/// The offset and length of an area in the ncm file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct NcmSection {
+    /// The offset from the beginning of the file
+    pub offset: u64,
+    /// The length of the area
+    pub length: u64,
+}
+
+impl NcmSection {
+    pub(crate) fn new(offset: u64, length: u64) -> Self {
+        Self { offset, length }
+    }
+
+    /// The offset right after the end of the area.
+    #[inline]
+    pub fn end(&self) -> u64 {
+        self.offset + self.length
+    }
+}
+
+/// The layout of the ncm file.
+///
+/// The offset of the area which has a length prefix is point to the data,
+/// not the length prefix.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct NcmLayout {
+    /// The magic header, it's always `CTENFDAM`
+    pub magic: NcmSection,
+    /// The version bytes after the magic header
+    pub version: NcmSection,
+    /// The encrypted key area
+    pub key: NcmSection,
+    /// The encrypted information area
+    pub info: NcmSection,
+    /// The CRC32 checksum of the header
+    pub checksum: NcmSection,
+    /// The gap byte after the checksum
+    pub gap: NcmSection,
+    /// The cover frame, it contains the image and the padding
+    pub cover_frame: NcmSection,
+    /// The image area
+    pub image: NcmSection,
+    /// The encrypted audio data, until the end of file
+    pub audio: NcmSection,
+}
+
+/// The size limits of the areas in the ncm file.
+///
+/// The lengths in the file header are not trusted, the area which is larger than
+/// the limit is rejected with [`Errors::LimitExceeded`] before any allocation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+    /// The max length of the key area, default is 1 KiB
+    pub max_key_length: u64,
+    /// The max length of the information area, default is 1 MiB
+    pub max_info_length: u64,
+    /// The max length of the cover frame, default is 64 MiB
+    pub max_image_length: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_key_length: 1024,
+            max_info_length: 1024 * 1024,
+            max_image_length: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl Limits {
+    fn check(section: Section, length: u64, limit: u64) -> Result<()> {
+        if length > limit {
+            return Err(Errors::LimitExceeded {
+                section,
+                length,
+                limit,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The source of the ncm header.
+///
+/// It's implemented for the byte slice, and for the reader when the `std` feature is enabled.
+pub(crate) trait HeaderSource {
+    /// Read the exact bytes of the section, the offset is used for the error context.
+    fn read_section(&mut self, buf: &mut [u8], section: Section, offset: u64) -> Result<()>;
+
+    /// Read the section with the untrusted length, the length should be checked by limits.
+    fn read_section_vec(&mut self, length: u64, section: Section, offset: u64) -> Result<Vec<u8>>;
+}
+
+impl HeaderSource for &[u8] {
+    fn read_section(&mut self, buf: &mut [u8], section: Section, offset: u64) -> Result<()> {
+        let data = self.read_section_vec(buf.len() as u64, section, offset)?;
+        buf.copy_from_slice(&data);
+        Ok(())
+    }
+
+    fn read_section_vec(&mut self, length: u64, section: Section, offset: u64) -> Result<Vec<u8>> {
+        if (self.len() as u64) < length {
+            return Err(Errors::Truncated {
+                section,
+                offset: offset + self.len() as u64,
+            });
+        }
+        let (data, rest) = self.split_at(length as usize);
+        *self = rest;
+        Ok(data.to_vec())
+    }
+}
+
+/// The parsed header of the ncm file.
+///
+/// It contains everything before the image data, and can decrypt the audio data
+/// without any reader.
+#[derive(Clone, Debug)]
+pub struct NcmHeader {
+    layout: NcmLayout,
+    info: Vec<u8>,
+    checksum: u32,
+    key_box: [u8; 256],
+}
+
+impl NcmHeader {
+    /// Parse the header from the beginning bytes of the ncm file.
+    ///
+    /// The data should contain at least everything before the image data.
+    /// The audio length of the layout is the length of audio data contained in the slice.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use ncmdump::NcmHeader;
+    /// #
+    /// let data = std::fs::read("res/test.ncm").expect("Can't read file");
+    /// let header = NcmHeader::parse(&data).unwrap();
+    /// let audio = header.layout().audio;
+    /// let mut music = data[audio.offset as usize..].to_vec();
+    /// header.decrypt(0, &mut music);
+    /// assert_eq!(music[..4], *b"fLaC");
+    /// ```
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Self::parse_with_limits(data, Limits::default())
+    }
+
+    /// Parse the header from the beginning bytes of the ncm file with the size limits of areas.
+    pub fn parse_with_limits(data: &[u8], limits: Limits) -> Result<Self> {
+        let mut source = data;
+        let mut header = Self::read(&mut source, limits)?;
+        let audio_start = header.layout.audio.offset;
+        header.layout.audio.length = (data.len() as u64).saturating_sub(audio_start);
+        Ok(header)
+    }
+
+    /// Read the header from the source, the audio length of the layout is zero.
+    pub(crate) fn read<S>(source: &mut S, limits: Limits) -> Result<Self>
+    where
+        S: HeaderSource,
+    {
+        let mut header = Self::read_key(source, limits)?;
+        header.read_sections(source, limits)?;
+        Ok(header)
+    }
+
+    /// Read the header from the source leniently, only the key is required.
+    ///
+    /// The first truncated section is returned, the sections after it are missing,
+    /// and the audio data is placed at the `end` of source with zero length.
+    #[cfg(feature = "std")]
+    pub(crate) fn salvage<S>(
+        source: &mut S,
+        limits: Limits,
+        end: u64,
+    ) -> Result<(Self, Option<Section>)>
+    where
+        S: HeaderSource,
+    {
+        let mut header = Self::read_key(source, limits)?;
+        let truncated = match header.read_sections(source, limits) {
+            Ok(()) if header.layout.audio.offset <= end => None,
+            Ok(()) if end < header.layout.image.end() => Some(Section::Image),
+            Ok(()) => Some(Section::Cover),
+            Err(err) if err.is_truncated() => err.section(),
+            Err(err) => return Err(err),
+        };
+        if truncated.is_some() {
+            header.layout.audio = NcmSection::new(end, 0);
+        }
+        Ok((header, truncated))
+    }
+
+    /// Read the format and the key, the other sections are empty.
+    fn read_key<S>(source: &mut S, limits: Limits) -> Result<Self>
+    where
+        S: HeaderSource,
+    {
+        // check format
+        let mut format = [0; 10];
+        source.read_section(&mut format, Section::Magic, 0)?;
+        if !Self::check_format(&format) {
+            return Err(Errors::InvalidFileType);
+        }
+
+        let mut key_length = [0; 4];
+        source.read_section(&mut key_length, Section::Key, 10)?;
+        let key_length = u32::from_le_bytes(key_length) as u64;
+        Limits::check(Section::Key, key_length, limits.max_key_length)?;
+        let key = source.read_section_vec(key_length, Section::Key, 14)?;
+        let key = Self::get_key(&key)?;
+        let key_box = Self::build_key_box(&key);
+
+        let layout = NcmLayout {
+            magic: NcmSection::new(0, 8),
+            version: NcmSection::new(8, 2),
+            key: NcmSection::new(14, key_length),
+            ..Default::default()
+        };
+        Ok(Self {
+            layout,
+            info: Vec::new(),
+            checksum: 0,
+            key_box,
+        })
+    }
+
+    /// Read the sections after the key, the layout is updated once the section is known.
+    fn read_sections<S>(&mut self, source: &mut S, limits: Limits) -> Result<()>
+    where
+        S: HeaderSource,
+    {
+        let info_length_start = self.layout.key.end();
+        let mut info_length = [0; 4];
+        source.read_section(&mut info_length, Section::Info, info_length_start)?;
+        let info_start = info_length_start + 4;
+        let info_length = u32::from_le_bytes(info_length) as u64;
+        Limits::check(Section::Info, info_length, limits.max_info_length)?;
+        self.layout.info = NcmSection::new(info_start, info_length);
+        self.info = source.read_section_vec(info_length, Section::Info, info_start)?;
+
+        let checksum_start = info_start + info_length;
+        self.layout.checksum = NcmSection::new(checksum_start, 4);
+        self.layout.gap = NcmSection::new(checksum_start + 4, 1);
+        let mut checksum = [0; 5];
+        source.read_section(&mut checksum, Section::Checksum, checksum_start)?;
+        self.checksum = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+
+        let mut cover_frame_len = [0; 4];
+        source.read_section(&mut cover_frame_len, Section::Cover, checksum_start + 5)?;
+        let cover_frame_len = u32::from_le_bytes(cover_frame_len) as u64;
+        Limits::check(Section::Cover, cover_frame_len, limits.max_image_length)?;
+
+        let mut image_length = [0; 4];
+        source.read_section(&mut image_length, Section::Image, checksum_start + 9)?;
+        let image_start = checksum_start + 13;
+        let image_length = u32::from_le_bytes(image_length) as u64;
+        if image_length > cover_frame_len {
+            return Err(Errors::InvalidImageLength);
+        }
+
+        self.layout.cover_frame = NcmSection::new(image_start, cover_frame_len);
+        self.layout.image = NcmSection::new(image_start, image_length);
+        self.layout.audio = NcmSection::new(image_start + cover_frame_len, 0);
+        Ok(())
+    }
+
+    /// Get the layout of the ncm file.
+    pub fn layout(&self) -> &NcmLayout {
+        &self.layout
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn layout_mut(&mut self) -> &mut NcmLayout {
+        &mut self.layout
+    }
+
+    /// Decrypt the audio data in place, the offset is relative to the start of audio data.
+    pub fn decrypt(&self, offset: u64, buffer: &mut [u8]) {
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            let j = ((offset + i as u64 + 1) & 0xff) as usize;
+            let k = (self.key_box[j].wrapping_add(j as u8)) as usize;
+            let key_index = self.key_box[k].wrapping_add(self.key_box[j]) as usize;
+            *byte ^= self.key_box[key_index]
+        }
+    }
+
+    /// Verify the CRC32 checksum with the beginning bytes of the ncm file.
+    ///
+    /// The data should contain at least everything before the checksum.
+    pub fn verify(&self, data: &[u8]) -> Result<()> {
+        let end = self.layout.info.end();
+        let header = data.get(..end as usize).ok_or(Errors::Truncated {
+            section: Section::Info,
+            offset: data.len() as u64,
+        })?;
+        let actual = crc32fast::hash(header);
+        if actual != self.checksum {
+            return Err(Errors::ChecksumMismatch {
+                expected: self.checksum,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Get the information bytes, which are start with `163 key(Don't modify):`.
+    fn get_info_bytes(&self) -> Vec<u8> {
+        self.info.iter().map(|item| item ^ 0x63).collect()
+    }
+
+    /// Get the original `163 key(Don't modify):...` comment string.
+    pub fn get_comment(&self) -> Result<String> {
+        let info_bytes = self.get_info_bytes();
+        String::from_utf8(info_bytes).map_err(|_| Errors::InfoDecodeError)
+    }
+
+    /// Decode the information buffer and return the information.
+    pub fn get_info(&self) -> Result<NcmInfo> {
+        let info = self.get_raw_info()?;
+        Ok(NcmInfo::from(info))
+    }
+
+    /// Decode the information buffer and return the raw information.
+    pub fn get_raw_info(&self) -> Result<RawNcmInfo> {
+        let info_tmp = self.get_info_bytes();
+        let info_key = info_tmp.get(22..).ok_or(Errors::InvalidInfoLength)?;
+        let info_key = STANDARD
+            .decode(info_key)
+            .map_err(|_| Errors::InfoDecodeError)?;
+        let info_data =
+            Self::decrypt_aes(&info_key, &INFO_KEY).map_err(|_| Errors::DecryptError {
+                section: Section::Info,
+                offset: self.layout.info.offset,
+            })?;
+        let info_data = info_data.get(6..).ok_or(Errors::InfoDecodeError)?;
+        let info_str =
+            String::from_utf8(info_data.to_vec()).map_err(|_| Errors::InfoDecodeError)?;
+        serde_json::from_str::<RawNcmInfo>(&info_str).map_err(|_| Errors::InfoDecodeError)
+    }
+
+    fn get_key(key: &[u8]) -> Result<Vec<u8>> {
+        let key_buffer = key.iter().map(|byte| byte ^ 0x64).collect::<Vec<u8>>();
+        let decrypt_buffer =
+            Self::decrypt_aes(&key_buffer, &HEADER_KEY).map_err(|_| Errors::DecryptError {
+                section: Section::Key,
+                offset: 14,
+            })?;
+        match decrypt_buffer.get(17..) {
+            Some(key) if !key.is_empty() => Ok(key.to_vec()),
+            _ => Err(Errors::InvalidKeyLength),
+        }
+    }
+
+    fn decrypt_aes(data: &[u8], key: &[u8; 16]) -> core::result::Result<Vec<u8>, UnpadError> {
+        Aes128::new(key.into()).decrypt_padded_vec_mut::<Pkcs7>(data)
+    }
+
+    fn build_key_box(key: &[u8]) -> [u8; 256] {
+        let mut j = 0;
+        let mut key_box = [0u8; 256];
+        key_box
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, k)| *k = i as u8);
+
+        let key_stream = key.iter().cycle();
+        for (i, &k) in (0..256).zip(key_stream) {
+            j = key_box[i].wrapping_add(j).wrapping_add(k);
+            key_box.swap(i, j as usize);
+        }
+        key_box
+    }
+
+    /// Check the file format by header.
+    fn check_format(buffer: &[u8]) -> bool {
+        buffer.starts_with(b"CTENFDAM")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header_ok() {
+        let data = include_bytes!("../res/test.ncm");
+        let header = NcmHeader::parse(data).unwrap();
+        assert_eq!(header.layout().audio, NcmSection::new(39682, 61440));
+        assert!(header.verify(data).is_ok());
+        assert!(header.get_comment().is_ok());
+        assert_eq!(header.get_info().unwrap().name, "寒鸦少年");
+
+        let mut music = data[39682..39686].to_vec();
+        header.decrypt(0, &mut music);
+        assert_eq!(music, b"fLaC");
+    }
+
+    #[test]
+    fn test_parse_header_prefix_ok() {
+        let data = include_bytes!("../res/test.ncm");
+        let header = NcmHeader::parse(&data[..673]).unwrap();
+        assert_eq!(header.layout().audio, NcmSection::new(39682, 0));
+    }
+
+    #[test]
+    fn test_parse_header_truncated_err() {
+        let data = include_bytes!("../res/test.ncm");
+        let result = NcmHeader::parse(&data[..400]);
+        assert!(matches!(
+            result,
+            Err(Errors::Truncated {
+                section: Section::Info,
+                offset: 400,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let key = [
+            0x23, 0x31, 0x34, 0x6C, 0x6A, 0x6B, 0x5F, 0x21, 0x5C, 0x5D, 0x26, 0x30, 0x55, 0x3C,
+            0x27, 0x28,
+        ];
+        let source = [
+            0x2F, 0xAE, 0x05, 0x53, 0x75, 0xB8, 0x63, 0x15, 0xF7, 0x64, 0x54, 0xE6, 0x6D, 0x9F,
+            0xBC, 0xFD, 0xD4, 0xC3, 0xE5, 0x4A, 0x5D, 0x7D, 0x61, 0x47, 0x93, 0x82, 0x00, 0xAC,
+            0x48, 0xE4, 0x2C, 0x96, 0x43, 0x0F, 0x53, 0x54, 0xBE, 0x36, 0xC8, 0x6D, 0xE5, 0x46,
+            0x47, 0x7E, 0x87, 0x88, 0xBC, 0xF6, 0x0F, 0x97, 0x0F, 0xA9, 0x60, 0xDB, 0xF7, 0x4C,
+            0xD1, 0xD0, 0xD4, 0x44, 0xEC, 0xF6, 0x89, 0x44, 0x6B, 0x88, 0xD3, 0x2A, 0x39, 0x25,
+            0xB8, 0x09, 0x71, 0xA9, 0x3F, 0xE0, 0xC1, 0x07, 0x22, 0x3A, 0xB6, 0x0B, 0xF0, 0xA1,
+            0xB3, 0x26, 0x96, 0x62, 0xBD, 0x30, 0x76, 0xB5, 0xDA, 0x03, 0x6B, 0xB0, 0x99, 0x43,
+            0x2E, 0x7B, 0x8F, 0xD4, 0xD2, 0x9E, 0xFF, 0x37, 0x24, 0xB5, 0x25, 0x56, 0x6A, 0x0B,
+            0x2B, 0x41, 0x19, 0x52, 0xF6, 0xC8, 0x6E, 0x56, 0xE8, 0xC4, 0xF0, 0xCA, 0xFC, 0x9F,
+            0x09, 0xA4, 0xED, 0x51, 0x94, 0x7E, 0xDD, 0xE4, 0xF1, 0x3D, 0x63, 0xEB, 0x7A, 0xBB,
+            0x8C, 0xE6, 0x8F, 0x42, 0xDA, 0xE1, 0x7F, 0x17, 0xB5, 0x06, 0xC8, 0x34, 0x1C, 0xD7,
+            0x4C, 0x7F, 0xBE, 0x7B, 0xE8, 0x66, 0xB1, 0x0C, 0xB6, 0x57, 0x65, 0x78, 0xCF, 0xD0,
+            0xBA, 0xCC, 0x78, 0x09, 0xC1, 0x0F, 0x50, 0xDE, 0x1A, 0x2A, 0x27, 0x5D, 0x83, 0x12,
+            0xBB, 0x3A, 0xA5, 0x12, 0xFB, 0x54, 0xA2, 0xC9, 0x4D, 0x15, 0x46, 0x9F, 0x36, 0x8D,
+            0xF6, 0x79, 0x98, 0xA7, 0x34, 0x7F, 0x84, 0x6D, 0xD9, 0xC7, 0x3C, 0x51, 0xA6, 0x8B,
+            0x95, 0x42, 0x09, 0x13, 0xA9, 0xAE, 0x2C, 0xE4, 0x5F, 0x8A, 0x26, 0x67, 0x3B, 0xF5,
+            0x01, 0x36, 0x0D, 0x20, 0xD0, 0x2C, 0xDE, 0xA1, 0xE2, 0x03, 0x62, 0xD3, 0xB8, 0x9F,
+            0x65, 0xE8, 0xD7, 0xC1, 0x05, 0x88, 0x83, 0x68, 0x50, 0xDB, 0xC7, 0x0F, 0xE5, 0x79,
+            0xF7, 0x3F, 0x37, 0x4A, 0xF4, 0xD9, 0x82, 0xB1, 0xB3, 0x04, 0x0B, 0xB0, 0xD8, 0x5C,
+            0xFA, 0x03, 0x9C, 0x63, 0xFF, 0xCA, 0xE2, 0xE4, 0x57, 0x08, 0x2A, 0x05, 0x34, 0x16,
+            0x03, 0xED, 0xC7, 0x85, 0xE0, 0x3C, 0x43, 0x53, 0x78, 0x8C, 0x88, 0x7F, 0x52, 0xE6,
+            0x94, 0x5D, 0xC7, 0x9A, 0x21, 0xD0, 0xEA, 0x89, 0x7B, 0x09, 0xB0, 0xDA, 0xA2, 0x0B,
+            0xEE, 0xC2, 0x66, 0x04, 0x2A, 0xA0, 0x9C, 0x28, 0xDC, 0xA9, 0xB4, 0x71, 0x90, 0xC8,
+            0x9B, 0x00, 0x7B, 0xF5, 0x7A, 0x9C, 0xAF, 0x2B, 0x8A, 0x7B, 0x0F, 0x70, 0x7F, 0x44,
+            0x01, 0x5A, 0xDB, 0x6D, 0x8E, 0x98, 0x3D, 0x4E, 0x14, 0x71, 0xC1, 0xB2, 0x0F, 0x66,
+            0x8F, 0x14, 0x0A, 0x5C, 0x78, 0xE9, 0x16, 0xD2, 0x45, 0x4F, 0x1F, 0xE3, 0x3F, 0x0E,
+            0xBD, 0x5D, 0x40, 0x75,
+        ];
+        let target = [
+            0x6D, 0x75, 0x73, 0x69, 0x63, 0x3A, 0x7B, 0x22, 0x6D, 0x75, 0x73, 0x69, 0x63, 0x49,
+            0x64, 0x22, 0x3A, 0x31, 0x33, 0x30, 0x35, 0x33, 0x36, 0x36, 0x35, 0x35, 0x36, 0x2C,
+            0x22, 0x6D, 0x75, 0x73, 0x69, 0x63, 0x4E, 0x61, 0x6D, 0x65, 0x22, 0x3A, 0x22, 0xE5,
+            0xAF, 0x92, 0xE9, 0xB8, 0xA6, 0xE5, 0xB0, 0x91, 0xE5, 0xB9, 0xB4, 0x22, 0x2C, 0x22,
+            0x61, 0x6C, 0x69, 0x61, 0x73, 0x22, 0x3A, 0x5B, 0x22, 0xE7, 0x94, 0xB5, 0xE8, 0xA7,
+            0x86, 0xE5, 0x89, 0xA7, 0xE3, 0x80, 0x8A, 0xE6, 0x96, 0x97, 0xE7, 0xA0, 0xB4, 0xE8,
+            0x8B, 0x8D, 0xE7, 0xA9, 0xB9, 0xE3, 0x80, 0x8B, 0xE4, 0xB8, 0xBB, 0xE9, 0xA2, 0x98,
+            0xE6, 0x9B, 0xB2, 0x22, 0x5D, 0x2C, 0x22, 0x61, 0x72, 0x74, 0x69, 0x73, 0x74, 0x22,
+            0x3A, 0x5B, 0x5B, 0x22, 0xE5, 0x8D, 0x8E, 0xE6, 0x99, 0xA8, 0xE5, 0xAE, 0x87, 0x22,
+            0x2C, 0x38, 0x36, 0x31, 0x37, 0x37, 0x37, 0x5D, 0x5D, 0x2C, 0x22, 0x61, 0x6C, 0x62,
+            0x75, 0x6D, 0x49, 0x64, 0x22, 0x3A, 0x37, 0x32, 0x37, 0x30, 0x36, 0x37, 0x38, 0x38,
+            0x2C, 0x22, 0x61, 0x6C, 0x62, 0x75, 0x6D, 0x22, 0x3A, 0x22, 0xE5, 0xAF, 0x92, 0xE9,
+            0xB8, 0xA6, 0xE5, 0xB0, 0x91, 0xE5, 0xB9, 0xB4, 0x22, 0x2C, 0x22, 0x61, 0x6C, 0x62,
+            0x75, 0x6D, 0x50, 0x69, 0x63, 0x44, 0x6F, 0x63, 0x49, 0x64, 0x22, 0x3A, 0x31, 0x30,
+            0x39, 0x39, 0x35, 0x31, 0x31, 0x36, 0x33, 0x35, 0x32, 0x30, 0x36, 0x33, 0x38, 0x35,
+            0x32, 0x32, 0x2C, 0x22, 0x61, 0x6C, 0x62, 0x75, 0x6D, 0x50, 0x69, 0x63, 0x22, 0x3A,
+            0x22, 0x68, 0x74, 0x74, 0x70, 0x3A, 0x2F, 0x2F, 0x70, 0x33, 0x2E, 0x6D, 0x75, 0x73,
+            0x69, 0x63, 0x2E, 0x31, 0x32, 0x36, 0x2E, 0x6E, 0x65, 0x74, 0x2F, 0x71, 0x52, 0x51,
+            0x54, 0x53, 0x5F, 0x54, 0x72, 0x6F, 0x5A, 0x6F, 0x39, 0x53, 0x4C, 0x56, 0x35, 0x79,
+            0x71, 0x70, 0x54, 0x35, 0x41, 0x3D, 0x3D, 0x2F, 0x31, 0x30, 0x39, 0x39, 0x35, 0x31,
+            0x31, 0x36, 0x33, 0x35, 0x32, 0x30, 0x36, 0x33, 0x38, 0x35, 0x32, 0x32, 0x2E, 0x6A,
+            0x70, 0x67, 0x22, 0x2C, 0x22, 0x6D, 0x76, 0x49, 0x64, 0x22, 0x3A, 0x30, 0x2C, 0x22,
+            0x62, 0x69, 0x74, 0x72, 0x61, 0x74, 0x65, 0x22, 0x3A, 0x39, 0x32, 0x33, 0x33, 0x37,
+            0x38, 0x2C, 0x22, 0x64, 0x75, 0x72, 0x61, 0x74, 0x69, 0x6F, 0x6E, 0x22, 0x3A, 0x33,
+            0x31, 0x35, 0x31, 0x34, 0x36, 0x2C, 0x22, 0x66, 0x6F, 0x72, 0x6D, 0x61, 0x74, 0x22,
+            0x3A, 0x22, 0x66, 0x6C, 0x61, 0x63, 0x22, 0x7D,
+        ];
+        let result = NcmHeader::decrypt_aes(&source, &key).unwrap();
+        assert_eq!(&result[..], &target);
+    }
+
+    #[test]
+    fn test_build_key_box() {
+        let key = [
+            0x31, 0x31, 0x38, 0x31, 0x39, 0x38, 0x30, 0x33, 0x33, 0x32, 0x38, 0x35, 0x45, 0x37,
+            0x66, 0x54, 0x34, 0x39, 0x78, 0x37, 0x64, 0x6F, 0x66, 0x39, 0x4F, 0x4B, 0x43, 0x67,
+            0x67, 0x39, 0x63, 0x64, 0x76, 0x68, 0x45, 0x75, 0x65, 0x7A, 0x79, 0x33, 0x69, 0x5A,
+            0x43, 0x4C, 0x31, 0x6E, 0x46, 0x76, 0x42, 0x46, 0x64, 0x31, 0x54, 0x34, 0x75, 0x53,
+            0x6B, 0x74, 0x41, 0x4A, 0x4B, 0x6D, 0x77, 0x5A, 0x58, 0x73, 0x69, 0x6A, 0x50, 0x62,
+            0x69, 0x6A, 0x6C, 0x69, 0x69, 0x6F, 0x6E, 0x56, 0x55, 0x58, 0x58, 0x67, 0x39, 0x70,
+            0x6C, 0x54, 0x62, 0x58, 0x45, 0x63, 0x6C, 0x41, 0x45, 0x39, 0x4C, 0x62,
+        ];
+        let key_box = [
+            0x43, 0x63, 0x9D, 0xE2, 0x5B, 0x4B, 0x55, 0xBB, 0x4C, 0xCF, 0x2A, 0x62, 0x0E, 0x48,
+            0x8A, 0x15, 0x59, 0x52, 0xBA, 0x6C, 0xEF, 0x6D, 0x72, 0x39, 0xA0, 0x9A, 0xA9, 0x27,
+            0x66, 0xBC, 0xF9, 0xC0, 0x47, 0xDF, 0x7D, 0xDE, 0x3B, 0x81, 0x04, 0xFF, 0x90, 0x77,
+            0x80, 0x50, 0x54, 0xBD, 0x0D, 0x58, 0x34, 0x0A, 0x44, 0xA8, 0x5F, 0x99, 0xC6, 0xBE,
+            0x4E, 0x4D, 0x13, 0x17, 0x83, 0x01, 0x35, 0x5C, 0xF4, 0x7B, 0x53, 0x31, 0x86, 0xD4,
+            0xB8, 0xAB, 0xD1, 0xB5, 0x68, 0xDC, 0x96, 0xF1, 0x9C, 0xE8, 0x7A, 0x1B, 0xB0, 0x56,
+            0x22, 0x1A, 0x51, 0x92, 0xBF, 0xFA, 0xB1, 0x19, 0x88, 0x26, 0x49, 0x08, 0xEB, 0xAC,
+            0x14, 0x28, 0xAD, 0x3A, 0x8C, 0x85, 0x84, 0x2C, 0x82, 0xB3, 0xA6, 0xA2, 0xA3, 0x12,
+            0x78, 0xA1, 0x57, 0xAE, 0x00, 0x2F, 0xB6, 0x61, 0xA5, 0x6F, 0x5A, 0x89, 0x29, 0x46,
+            0x2E, 0x4F, 0x36, 0x40, 0x07, 0x87, 0xA7, 0x65, 0x73, 0xC4, 0x7C, 0x33, 0x1E, 0xE5,
+            0x10, 0xB4, 0xFD, 0xC9, 0xE0, 0xB7, 0x97, 0x32, 0x5D, 0x64, 0x41, 0xF0, 0x20, 0xC3,
+            0x95, 0xFE, 0xD2, 0x21, 0xFB, 0x75, 0x3D, 0x0B, 0x3E, 0xF2, 0xD5, 0xCB, 0xD6, 0xF7,
+            0x1F, 0x24, 0x45, 0x69, 0xB9, 0xDA, 0x6A, 0x76, 0x03, 0xF8, 0x70, 0x8E, 0xC1, 0xC8,
+            0xD7, 0x4A, 0xD0, 0x9E, 0xCD, 0xA4, 0xCE, 0xAA, 0x1D, 0xED, 0xF6, 0x02, 0x60, 0xE3,
+            0xDB, 0x8D, 0x09, 0xF3, 0x37, 0xE1, 0xC5, 0xCA, 0x8F, 0x2D, 0x7F, 0x74, 0x42, 0x6E,
+            0x8B, 0x3F, 0x23, 0xC2, 0xD3, 0xCC, 0xD9, 0xEE, 0x98, 0xE6, 0x11, 0x05, 0xEA, 0xD8,
+            0xB2, 0xE4, 0xF5, 0xE7, 0x71, 0x2B, 0x93, 0x9B, 0x3C, 0x30, 0xE9, 0xC7, 0x38, 0xEC,
+            0x18, 0x6B, 0x79, 0xFC, 0xAF, 0x5E, 0x9F, 0x7E, 0x91, 0xDD, 0x16, 0x94, 0x0F, 0x06,
+            0x67, 0x25, 0x0C, 0x1C,
+        ];
+        assert_eq!(NcmHeader::build_key_box(&key), key_box);
+    }
+}
@@ -37,18 +37,53 @@
 //! }
 //! ```
 //!
+//! # Features
+//!
+//! The `std` feature is enabled by default. Without it, the crate is `no_std` and only
+//! needs `alloc`, the [`NcmHeader`] and [`QmcCipher`] can still parse the header and
+//! decrypt the data in byte slices.
+//!
+//! The `tag` feature enables the [`tag`] module, which writes the id3 tag and the
+//! vorbis comments into the decrypted music, and the one-call [`convert`] function.
+//!
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "tag")]
+pub use crate::convert::{convert, ConvertOptions, ConvertReport};
 #[cfg(feature = "ncmdump")]
-pub use crate::ncmdump::NcmInfo;
+pub use crate::header::{Limits, NcmHeader, NcmLayout, NcmSection};
 #[cfg(feature = "ncmdump")]
+pub use crate::ncmdump::NcmInfo;
+#[cfg(all(feature = "ncmdump", feature = "std"))]
 pub use crate::ncmdump::Ncmdump;
+#[cfg(feature = "ncmdump")]
+pub use crate::ncmdump::{NcmId, RawNcmInfo};
+#[cfg(feature = "std")]
+pub use crate::progress::{CancelToken, Progress};
 #[cfg(feature = "qmcdump")]
+pub use crate::qmcdump::QmcCipher;
+#[cfg(all(feature = "qmcdump", feature = "std"))]
 pub use crate::qmcdump::QmcDump;
+#[cfg(all(feature = "ncmdump", feature = "std"))]
+pub use crate::salvage::SalvageReport;
 
+#[cfg(feature = "tag")]
+mod convert;
+#[cfg(feature = "ncmdump")]
+mod header;
 #[cfg(feature = "ncmdump")]
 mod ncmdump;
+#[cfg(feature = "std")]
+mod progress;
 #[cfg(feature = "qmcdump")]
 mod qmcdump;
+#[cfg(all(feature = "ncmdump", feature = "std"))]
+mod salvage;
 
 pub mod error;
+#[cfg(feature = "tag")]
+pub mod tag;
 #[cfg(feature = "utils")]
 pub mod utils;
@@ -1,21 +1,23 @@
-use std::io::{Read, Seek, SeekFrom, Write};
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "std")]
+use std::sync::Arc;
 
-use aes::Aes128;
-use base64::engine::general_purpose::STANDARD;
-use base64::Engine;
-use cipher::block_padding::Pkcs7;
-use cipher::{BlockDecryptMut, KeyInit};
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "std")]
+use crate::error::Section;
 use crate::error::{Errors, Result};
-
-const HEADER_KEY: [u8; 16] = [
-    0x68, 0x7A, 0x48, 0x52, 0x41, 0x6D, 0x73, 0x6F, 0x35, 0x6B, 0x49, 0x6E, 0x62, 0x61, 0x78, 0x57,
-];
-
-const INFO_KEY: [u8; 16] = [
-    0x23, 0x31, 0x34, 0x6C, 0x6A, 0x6B, 0x5F, 0x21, 0x5C, 0x5D, 0x26, 0x30, 0x55, 0x3C, 0x27, 0x28,
-];
+#[cfg(feature = "std")]
+use crate::header::{HeaderSource, Limits, NcmHeader, NcmLayout, NcmSection};
+#[cfg(feature = "std")]
+use crate::progress::{self, CancelToken, Progress};
+#[cfg(feature = "std")]
+use crate::salvage::SalvageReport;
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(untagged)]
@@ -31,16 +33,21 @@ pub struct RawNcmInfo {
     #[serde(rename = "musicName")]
     pub name: String,
     /// The id of music
-    #[serde(rename = "musicId")]
-    pub id: NcmId,
+    #[serde(default, rename = "musicId")]
+    pub id: Option<NcmId>,
     /// The album of music, it's an url
     pub album: String,
+    /// The id of album
+    #[serde(rename = "albumId")]
+    pub album_id: Option<NcmId>,
     /// The artist of music, first item is name, second item is id
     pub artist: Vec<(String, NcmId)>,
     // The bit rate of music
-    pub bitrate: NcmId,
+    #[serde(default)]
+    pub bitrate: Option<NcmId>,
     /// The duration of music
-    pub duration: NcmId,
+    #[serde(default)]
+    pub duration: Option<NcmId>,
     /// The format of music, is maybe 'mp3' or 'flac'
     pub format: String,
     /// The id of MV
@@ -48,67 +55,133 @@ pub struct RawNcmInfo {
     pub mv_id: Option<NcmId>,
     /// The alias of music
     pub alias: Option<Vec<String>>,
+    /// The translated names of music
+    #[serde(rename = "transNames")]
+    pub trans_names: Option<Vec<String>>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// The ncm file information.
+///
+/// The value which is missing or can't be parsed is `None`.
+/// Use `NcmInfo::try_from(&raw_info)` to tell the invalid value apart from the missing value.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct NcmInfo {
     pub name: String,
     /// The id of music
-    pub id: u64,
+    pub id: Option<u64>,
     /// The album of music, it's an url
     pub album: String,
+    /// The id of album
+    pub album_id: Option<u64>,
     /// The artist of music, first item is name, second item is id
-    pub artist: Vec<(String, u64)>,
+    pub artist: Vec<(String, Option<u64>)>,
     // The bit rate of music
-    pub bitrate: u64,
+    pub bitrate: Option<u64>,
     /// The duration of music
-    pub duration: u64,
+    pub duration: Option<u64>,
     /// The format of music, is maybe 'mp3' or 'flac'
     pub format: String,
     /// The id of MV
     pub mv_id: Option<u64>,
     /// The alias of music
     pub alias: Option<Vec<String>>,
+    /// The translated names of music
+    pub trans_names: Option<Vec<String>>,
 }
 
 /// The ncm file dump wrapper.
+#[cfg(feature = "std")]
 pub struct Ncmdump<S>
 where
     S: Read,
 {
     reader: S,
     cursor: u64,
-    info: (u64, u64),
-    image: (u64, u64),
-    key_box: [u8; 256],
+    header: Arc<NcmHeader>,
 }
 
 impl From<RawNcmInfo> for NcmInfo {
     fn from(raw_info: RawNcmInfo) -> Self {
         Self {
             name: raw_info.name,
-            id: raw_info.id.get_id().unwrap_or(0),
+            id: raw_info
+                .id
+                .and_then(|id| id.parse("musicId").ok().flatten()),
             album: raw_info.album,
+            album_id: raw_info
+                .album_id
+                .and_then(|id| id.parse("albumId").ok().flatten()),
             artist: raw_info
                 .artist
                 .into_iter()
-                .map(|(name, id)| (name, id.get_id().unwrap_or(0)))
-                .collect::<Vec<(String, u64)>>(),
-            bitrate: raw_info.bitrate.get_id().unwrap_or(0),
-            duration: raw_info.duration.get_id().unwrap_or(0),
+                .map(|(name, id)| (name, id.parse("artist").ok().flatten()))
+                .collect::<Vec<(String, Option<u64>)>>(),
+            bitrate: raw_info
+                .bitrate
+                .and_then(|bitrate| bitrate.parse("bitrate").ok().flatten()),
+            duration: raw_info
+                .duration
+                .and_then(|duration| duration.parse("duration").ok().flatten()),
             format: raw_info.format,
-            mv_id: match raw_info.mv_id {
-                Some(id) => match id.get_id() {
-                    Ok(inner) => Some(inner),
-                    Err(_) => None,
-                },
-                None => None,
-            },
+            mv_id: raw_info
+                .mv_id
+                .and_then(|id| id.parse("mvId").ok().flatten()),
             alias: raw_info.alias,
+            trans_names: raw_info.trans_names,
         }
     }
 }
 
+/// The strict conversion, return an error if any value is present but can't be parsed.
+impl TryFrom<&RawNcmInfo> for NcmInfo {
+    type Error = Errors;
+
+    fn try_from(raw_info: &RawNcmInfo) -> Result<Self> {
+        let artist = raw_info
+            .artist
+            .iter()
+            .enumerate()
+            .map(|(index, (name, id))| {
+                let id = id.parse(&format!("artist[{}]", index))?;
+                Ok((name.to_owned(), id))
+            })
+            .collect::<Result<Vec<(String, Option<u64>)>>>()?;
+        let mv_id = match &raw_info.mv_id {
+            Some(id) => id.parse("mvId")?,
+            None => None,
+        };
+        let album_id = match &raw_info.album_id {
+            Some(id) => id.parse("albumId")?,
+            None => None,
+        };
+        let id = match &raw_info.id {
+            Some(id) => id.parse("musicId")?,
+            None => None,
+        };
+        let bitrate = match &raw_info.bitrate {
+            Some(bitrate) => bitrate.parse("bitrate")?,
+            None => None,
+        };
+        let duration = match &raw_info.duration {
+            Some(duration) => duration.parse("duration")?,
+            None => None,
+        };
+        Ok(Self {
+            name: raw_info.name.to_owned(),
+            id,
+            album: raw_info.album.to_owned(),
+            album_id,
+            artist,
+            bitrate,
+            duration,
+            format: raw_info.format.to_owned(),
+            mv_id,
+            alias: raw_info.alias.to_owned(),
+            trans_names: raw_info.trans_names.to_owned(),
+        })
+    }
+}
+
 impl NcmId {
     pub fn get_id(self) -> Result<u64> {
         match self {
@@ -121,61 +194,61 @@ impl NcmId {
             NcmId::Integer(num) => Ok(num),
         }
     }
+
+    /// Parse the id of the field, the empty string is treated as a missing value.
+    fn parse(&self, field: &str) -> Result<Option<u64>> {
+        match self {
+            NcmId::String(s) if s.is_empty() => Ok(None),
+            NcmId::String(s) => s.parse().map(Some).map_err(|err| Errors::InvalidField {
+                field: field.to_string(),
+                reason: format!("{:?} is not a valid id, {}", s, err),
+            }),
+            NcmId::Integer(num) => Ok(Some(*num)),
+        }
+    }
 }
 
+#[cfg(feature = "std")]
 impl<S> Ncmdump<S>
 where
     S: Read,
 {
     #[inline]
     fn base(&self) -> u64 {
-        self.image.0 + self.image.1
+        self.header.layout().audio.offset
     }
 
-    fn get_key(key: &[u8]) -> Result<Vec<u8>> {
-        let key_buffer = key.iter().map(|byte| byte ^ 0x64).collect::<Vec<u8>>();
-        let decrypt_buffer = Self::decrypt(&key_buffer, &HEADER_KEY)?;
-        Ok(decrypt_buffer[17..].to_vec())
-    }
-
-    fn encrypt(&mut self, offset: u64, buffer: &mut [u8]) {
-        for (i, byte) in buffer.iter_mut().enumerate() {
-            let j = ((offset + i as u64 + 1) & 0xff) as usize;
-            let k = (self.key_box[j as usize].wrapping_add(j as u8)) as usize;
-            let key_index = self.key_box[k].wrapping_add(self.key_box[j]) as usize;
-            *byte ^= self.key_box[key_index]
-        }
-    }
-
-    fn decrypt(data: &[u8], key: &[u8; 16]) -> Result<Vec<u8>> {
-        let result = Aes128::new(key.into())
-            .decrypt_padded_vec_mut::<Pkcs7>(data)
-            .map_err(|_| Errors::DecryptError)?;
-        Ok(result)
+    /// Get the layout of the ncm file.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::fs::File;
+    /// #
+    /// # use ncmdump::Ncmdump;
+    /// #
+    /// let file = File::open("res/test.ncm").expect("Can't open file");
+    /// let ncm = Ncmdump::from_reader(file).unwrap();
+    /// let layout = ncm.layout();
+    /// assert_eq!(layout.key.length, 128);
+    /// ```
+    pub fn layout(&self) -> &NcmLayout {
+        self.header.layout()
     }
 
-    fn build_key_box(key: &[u8]) -> [u8; 256] {
-        let mut j = 0;
-        let mut key_box = [0u8; 256];
-        key_box
-            .iter_mut()
-            .enumerate()
-            .for_each(|(i, k)| *k = i as u8);
-
-        let key_stream = key.iter().cycle();
-        for (i, &k) in (0..256).zip(key_stream) {
-            j = key_box[i].wrapping_add(j).wrapping_add(k);
-            key_box.swap(i, j as usize);
-        }
-        key_box
+    /// Get the parsed header of the ncm file.
+    pub fn header(&self) -> &NcmHeader {
+        &self.header
     }
 
-    /// Check the file format by header.
-    fn check_format(buffer: &[u8]) -> bool {
-        buffer.starts_with(b"CTENFDAM")
+    /// Get the shared parsed header of the ncm file, which can be attached to other
+    /// readers of the same file by [`Ncmdump::with_header`] without parsing again.
+    pub fn shared_header(&self) -> Arc<NcmHeader> {
+        Arc::clone(&self.header)
     }
 }
 
+#[cfg(feature = "std")]
 impl<S> Ncmdump<S>
 where
     S: Read + Seek,
@@ -208,68 +281,199 @@ where
     /// let cursor = Cursor::new(data);
     /// let _ = Ncmdump::from_reader(cursor).unwrap();
     /// ```
-    pub fn from_reader(mut reader: S) -> Result<Self> {
-        // check format
-        let mut format = [0; 10];
-        reader
-            .read_exact(&mut format)
-            .map_err(|_| Errors::InvalidFileType)?;
-        if !Self::check_format(&format) {
-            return Err(Errors::InvalidFileType);
+    pub fn from_reader(reader: S) -> Result<Self> {
+        Self::from_reader_with_limits(reader, Limits::default())
+    }
+
+    /// Create a Ncmdump from a seekable reader with the size limits of areas.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::fs::File;
+    /// #
+    /// # use ncmdump::{Limits, Ncmdump};
+    /// #
+    /// let file = File::open("res/test.ncm").expect("Can't open file");
+    /// let limits = Limits {
+    ///     max_image_length: 1024 * 1024,
+    ///     ..Default::default()
+    /// };
+    /// let _ = Ncmdump::from_reader_with_limits(file, limits).unwrap();
+    /// ```
+    pub fn from_reader_with_limits(mut reader: S, limits: Limits) -> Result<Self> {
+        let mut header = NcmHeader::read(&mut ReaderSource(&mut reader), limits)?;
+        let audio_start = header.layout().audio.offset;
+        let audio_end = reader.seek(SeekFrom::End(0))?;
+        if audio_end < audio_start {
+            return Err(Errors::Read {
+                section: Section::Cover,
+                offset: audio_end,
+                source: io::ErrorKind::UnexpectedEof.into(),
+            });
         }
+        reader.seek(SeekFrom::Start(audio_start))?;
+        header.layout_mut().audio.length = audio_end - audio_start;
+        Ok(Self {
+            reader,
+            cursor: 0,
+            header: Arc::new(header),
+        })
+    }
 
-        let mut key_length = [0; 4];
-        reader
-            .read_exact(&mut key_length)
-            .map_err(|_| Errors::InvalidKeyLength)?;
-        let key_length = u32::from_le_bytes(key_length) as usize;
-        let mut key = vec![0u8; key_length];
-        reader
-            .read_exact(&mut key)
-            .map_err(|_| Errors::InvalidKeyLength)?;
-        let key = Self::get_key(&key)?;
-        let key_box = Self::build_key_box(&key);
-
-        // reader.seek(SeekFrom::Current(key_length as i64))?;
-        let mut info_length = [0; 4];
-        reader
-            .read_exact(&mut info_length)
-            .map_err(|_| Errors::InvalidInfoLength)?;
-        let info_start = reader.stream_position()?;
-        let info_length = u32::from_le_bytes(info_length) as u64;
-
-        reader.seek(SeekFrom::Current(info_length as i64))?;
-        reader.seek(SeekFrom::Current(5))?;
-        let mut cover_frame_len = [0; 4];
-        reader.read_exact(&mut cover_frame_len)?;
-        let cover_frame_len = u32::from_le_bytes(cover_frame_len) as u64;
-
-        let mut image_length = [0; 4];
-        reader
-            .read_exact(&mut image_length)
-            .map_err(|_| Errors::InvalidImageLength)?;
-        let image_start = reader.stream_position()?;
-        let image_length = u32::from_le_bytes(image_length) as u64;
-
-        reader.seek(SeekFrom::Start(image_start + cover_frame_len))?;
+    /// Create a Ncmdump from a truncated or partially downloaded file leniently.
+    ///
+    /// Only the key is required, the missing sections are marked in the report, and the
+    /// audio data is empty if the header is truncated. The information and image are
+    /// unavailable when their sections are missing.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::io::Cursor;
+    /// #
+    /// # use ncmdump::error::Section;
+    /// # use ncmdump::Ncmdump;
+    /// #
+    /// let mut data = std::fs::read("res/test.ncm").expect("Can't read file");
+    /// data.truncate(20000);
+    /// let (mut ncm, report) = Ncmdump::salvage(Cursor::new(data)).unwrap();
+    /// assert_eq!(report.truncated, Some(Section::Image));
+    /// assert!(report.is_missing(Section::Audio));
+    /// assert!(ncm.get_info().is_ok());
+    /// assert!(ncm.get_data().unwrap().is_empty());
+    /// ```
+    pub fn salvage(reader: S) -> Result<(Self, SalvageReport)> {
+        Self::salvage_with_limits(reader, Limits::default())
+    }
+
+    /// Create a Ncmdump from a truncated file leniently with the size limits of areas.
+    pub fn salvage_with_limits(mut reader: S, limits: Limits) -> Result<(Self, SalvageReport)> {
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let (mut header, truncated) =
+            NcmHeader::salvage(&mut ReaderSource(&mut reader), limits, end)?;
+        let audio_start = header.layout().audio.offset;
+        reader.seek(SeekFrom::Start(audio_start))?;
+        header.layout_mut().audio.length = end - audio_start;
+        let info = match truncated {
+            Some(section) if section <= Section::Info => None,
+            _ => header.get_info().ok(),
+        };
+        let report = SalvageReport::new(truncated, end - audio_start, info.as_ref());
+        let dump = Self {
+            reader,
+            cursor: 0,
+            header: Arc::new(header),
+        };
+        Ok((dump, report))
+    }
+
+    /// Create a Ncmdump from a parsed header and a new reader of the same file.
+    ///
+    /// The header isn't parsed again, the reader is only seeked to the start of the
+    /// music data, so it's cheap to create a Ncmdump for every concurrent reader.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::fs::File;
+    /// # use std::thread;
+    /// #
+    /// # use ncmdump::Ncmdump;
+    /// #
+    /// let file = File::open("res/test.ncm").expect("Can't open file");
+    /// let ncm = Ncmdump::from_reader(file).unwrap();
+    /// let header = ncm.shared_header();
+    ///
+    /// let tasks: Vec<_> = (0..2)
+    ///     .map(|_| {
+    ///         let header = header.clone();
+    ///         thread::spawn(move || {
+    ///             let file = File::open("res/test.ncm").expect("Can't open file");
+    ///             let mut ncm = Ncmdump::with_header(header, file).unwrap();
+    ///             ncm.get_data().unwrap()
+    ///         })
+    ///     })
+    ///     .collect();
+    /// for task in tasks {
+    ///     assert_eq!(task.join().unwrap().len(), 61440);
+    /// }
+    /// ```
+    pub fn with_header(header: Arc<NcmHeader>, mut reader: S) -> Result<Self> {
+        reader.seek(SeekFrom::Start(header.layout().audio.offset))?;
         Ok(Self {
             reader,
-            key_box,
             cursor: 0,
-            info: (info_start, info_length),
-            image: (image_start, image_length),
+            header,
         })
     }
 
-    /// Utils for get bytes.
+    /// Utils for get bytes, the reader is seeked back to the music data afterwards, so
+    /// it doesn't break the reading of music.
     fn get_bytes(&mut self, start: u64, length: u64) -> Result<Vec<u8>> {
-        let reader = self.reader.by_ref();
         let mut buf = Vec::new();
-        reader.seek(SeekFrom::Start(start))?;
-        reader.take(length).read_to_end(&mut buf)?;
+        let result = self
+            .reader
+            .seek(SeekFrom::Start(start))
+            .and_then(|_| self.reader.by_ref().take(length).read_to_end(&mut buf));
+        self.reader
+            .seek(SeekFrom::Start(self.base() + self.cursor))?;
+        result?;
         Ok(buf)
     }
 
+    /// Verify the CRC32 checksum of the file header.
+    ///
+    /// The checksum covers everything from the beginning of the file to the end of
+    /// the information area, so a corrupted or modified file will be reported as
+    /// [`Errors::ChecksumMismatch`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::fs::File;
+    ///
+    /// use anyhow::Result;
+    /// use ncmdump::Ncmdump;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let file = File::open("res/test.ncm")?;
+    ///     let mut ncm = Ncmdump::from_reader(file)?;
+    ///     ncm.verify()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn verify(&mut self) -> Result<()> {
+        let data = self.get_bytes(0, self.layout().info.end())?;
+        self.header.verify(&data)
+    }
+
+    /// Get the original `163 key(Don't modify):...` comment string.
+    ///
+    /// The netease client use this string to recognise a local file as a known song,
+    /// so it's usually written into the comment tag of the output file.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::fs::File;
+    ///
+    /// use anyhow::Result;
+    /// use ncmdump::Ncmdump;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let file = File::open("res/test.ncm")?;
+    ///     let mut ncm = Ncmdump::from_reader(file)?;
+    ///     let comment = ncm.get_comment()?;
+    ///     assert!(comment.starts_with("163 key(Don't modify):"));
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_comment(&mut self) -> Result<String> {
+        self.header.get_comment()
+    }
+
     /// Decode the information buffer and just return the information.
     ///
     /// # Example
@@ -290,21 +494,30 @@ where
     /// }
     /// ```
     pub fn get_info(&mut self) -> Result<NcmInfo> {
-        let (start, length) = self.info;
-        let info_bytes = self.get_bytes(start, length)?;
-        let info_tmp = info_bytes
-            .iter()
-            .map(|item| item ^ 0x63)
-            .collect::<Vec<u8>>();
-        let info_key = STANDARD
-            .decode(&info_tmp[22..])
-            .map_err(|_| Errors::InfoDecodeError)?;
-        let info_data = Self::decrypt(&info_key, &INFO_KEY)?;
-        let info_str =
-            String::from_utf8(info_data[6..].to_vec()).map_err(|_| Errors::InfoDecodeError)?;
-        let info =
-            serde_json::from_str::<RawNcmInfo>(&info_str).map_err(|_| Errors::InfoDecodeError)?;
-        Ok(NcmInfo::from(info))
+        self.header.get_info()
+    }
+
+    /// Decode the information buffer and return the raw information.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::fs::File;
+    ///
+    /// use anyhow::Result;
+    /// use ncmdump::{NcmInfo, Ncmdump};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let file = File::open("res/test.ncm")?;
+    ///     let mut ncm = Ncmdump::from_reader(file)?;
+    ///     let raw_info = ncm.get_raw_info()?;
+    ///     let info = NcmInfo::try_from(&raw_info)?;
+    ///     println!("{:?}", info);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_raw_info(&mut self) -> Result<RawNcmInfo> {
+        self.header.get_raw_info()
     }
 
     /// Get the image bytes from ncmdump, if it's exists.
@@ -333,8 +546,8 @@ where
     /// }
     /// ```
     pub fn get_image(&mut self) -> Result<Vec<u8>> {
-        let (start, end) = self.image;
-        let image = self.get_bytes(start, end)?;
+        let NcmSection { offset, length } = self.layout().image;
+        let image = self.get_bytes(offset, length)?;
         Ok(image)
     }
 
@@ -365,47 +578,152 @@ where
     /// ```
     pub fn get_data(&mut self) -> Result<Vec<u8>> {
         let mut data = Vec::new();
-        let mut buffer = [0; 0x8000];
-        while let Ok(size) = self.read(&mut buffer) {
-            if size == 0 {
-                break;
-            }
-            data.write_all(&buffer[..size])?;
-        }
+        self.copy_to(&mut data)?;
+        Ok(data)
+    }
+
+    /// Decrypt the music data from ncmdump into the writer, and return the size of bytes
+    /// written. Any read or write error is returned, instead of a truncated music.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use std::fs::File;
+    ///
+    /// use anyhow::Result;
+    /// use ncmdump::Ncmdump;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let file = File::open("res/test.ncm")?;
+    ///     let mut ncm = Ncmdump::from_reader(file)?;
+    ///     let mut target = File::create("res/test.flac")?;
+    ///     let size = ncm.copy_to(&mut target)?;
+    ///     assert_eq!(size, 61440);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn copy_to<W>(&mut self, writer: &mut W) -> Result<u64>
+    where
+        W: Write + ?Sized,
+    {
+        let (start, total) = (self.cursor, self.layout().audio.length);
+        progress::copy(self, writer, start, total, &|_, _| {}, &CancelToken::new())
+    }
+
+    /// Get the music data from ncmdump, report the progress and check the cancellation
+    /// between chunks.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use std::fs::File;
+    ///
+    /// use anyhow::Result;
+    /// use ncmdump::{CancelToken, Ncmdump};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let file = File::open("res/test.ncm")?;
+    ///     let mut ncm = Ncmdump::from_reader(file)?;
+    ///     let progress = |done, total| println!("{}/{}", done, total);
+    ///     let music = ncm.get_data_with_progress(&progress, &CancelToken::new())?;
+    ///     assert_eq!(music.len(), 61440);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_data_with_progress<P>(
+        &mut self,
+        progress: &P,
+        cancel: &CancelToken,
+    ) -> Result<Vec<u8>>
+    where
+        P: Progress + ?Sized,
+    {
+        let mut data = Vec::new();
+        let (start, total) = (self.cursor, self.layout().audio.length);
+        progress::copy(self, &mut data, start, total, progress, cancel)?;
         Ok(data)
     }
 }
 
+#[cfg(feature = "std")]
 impl<R> Read for Ncmdump<R>
 where
     R: Read + Seek,
 {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let size = self.reader.read(buf)?;
-        self.encrypt(self.cursor, buf);
+        self.header.decrypt(self.cursor, &mut buf[..size]);
         self.cursor += size as u64;
         Ok(size)
     }
 }
 
+#[cfg(feature = "std")]
 impl<R> Seek for Ncmdump<R>
 where
     R: Read + Seek,
 {
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
         let base = self.base();
-        let pos = match pos {
-            SeekFrom::Start(p) => SeekFrom::Start(p + base),
-            _ => pos,
+        let target = match pos {
+            SeekFrom::Start(p) => base.checked_add(p),
+            SeekFrom::Current(p) => (base + self.cursor).checked_add_signed(p),
+            SeekFrom::End(p) => self.layout().audio.end().checked_add_signed(p),
         };
-        self.cursor = self.reader.seek(pos)? - base;
+        let target = target.filter(|target| *target >= base).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        self.cursor = self.reader.seek(SeekFrom::Start(target))? - base;
         Ok(self.cursor)
     }
 }
 
-#[cfg(test)]
+/// The header source of the std reader.
+#[cfg(feature = "std")]
+struct ReaderSource<'a, R>(&'a mut R);
+
+#[cfg(feature = "std")]
+impl<R> HeaderSource for ReaderSource<'_, R>
+where
+    R: Read,
+{
+    fn read_section(&mut self, buf: &mut [u8], section: Section, offset: u64) -> Result<()> {
+        self.0.read_exact(buf).map_err(|source| Errors::Read {
+            section,
+            offset,
+            source,
+        })
+    }
+
+    fn read_section_vec(&mut self, length: u64, section: Section, offset: u64) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.0
+            .by_ref()
+            .take(length)
+            .read_to_end(&mut buf)
+            .map_err(|source| Errors::Read {
+                section,
+                offset,
+                source,
+            })?;
+        if (buf.len() as u64) < length {
+            return Err(Errors::Read {
+                section,
+                offset: offset + buf.len() as u64,
+                source: io::ErrorKind::UnexpectedEof.into(),
+            });
+        }
+        Ok(buf)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 pub mod tests {
     use std::fs::File;
+    use std::io::Cursor;
 
     use anyhow::Result;
 
@@ -428,36 +746,260 @@ pub mod tests {
             info,
             NcmInfo {
                 name: "寒鸦少年".to_string(),
-                id: 1305366556,
+                id: Some(1305366556),
                 album: "寒鸦少年".to_string(),
-                artist: vec![("华晨宇".into(), 861777)],
-                bitrate: 923378,
-                duration: 315146,
+                album_id: Some(72706788),
+                artist: vec![("华晨宇".into(), Some(861777))],
+                bitrate: Some(923378),
+                duration: Some(315146),
                 format: "flac".to_string(),
                 mv_id: Some(0),
                 alias: Some(vec!["电视剧《斗破苍穹》主题曲".into()]),
+                trans_names: None,
             },
         );
         Ok(())
     }
 
+    #[test]
+    fn test_layout_ok() -> Result<()> {
+        let reader = File::open("res/test.ncm")?;
+        let ncm = Ncmdump::from_reader(reader)?;
+        assert_eq!(
+            ncm.layout(),
+            &NcmLayout {
+                magic: NcmSection::new(0, 8),
+                version: NcmSection::new(8, 2),
+                key: NcmSection::new(14, 128),
+                info: NcmSection::new(146, 514),
+                checksum: NcmSection::new(660, 4),
+                gap: NcmSection::new(664, 1),
+                cover_frame: NcmSection::new(673, 39009),
+                image: NcmSection::new(673, 39009),
+                audio: NcmSection::new(39682, 61440),
+            },
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_dump_truncated_err() -> Result<()> {
+        let data = std::fs::read("res/test.ncm")?;
+        let result = Ncmdump::from_reader(Cursor::new(data[..400].to_vec()));
+        let err = result.err().unwrap();
+        assert!(err.is_truncated());
+        assert!(matches!(
+            err,
+            Errors::Read {
+                section: Section::Info,
+                offset: 400,
+                ..
+            }
+        ));
+
+        let result = Ncmdump::from_reader(Cursor::new(data[..20000].to_vec()));
+        let err = result.err().unwrap();
+        assert!(err.is_truncated());
+        assert!(matches!(
+            err,
+            Errors::Read {
+                section: Section::Cover,
+                ..
+            }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_dump_wrong_key_err() -> Result<()> {
+        let mut data = std::fs::read("res/test.ncm")?;
+        // Modify the last block of the key area.
+        data[140] ^= 0xFF;
+        let result = Ncmdump::from_reader(Cursor::new(data));
+        let err = result.err().unwrap();
+        assert!(err.is_wrong_key());
+        assert!(!err.is_truncated());
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_dump_limit_err() -> Result<()> {
+        let mut data = std::fs::read("res/test.ncm")?;
+        let result = Ncmdump::from_reader_with_limits(
+            Cursor::new(data.clone()),
+            Limits {
+                max_image_length: 1024,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(Errors::LimitExceeded {
+                section: Section::Cover,
+                length: 39009,
+                limit: 1024,
+            })
+        ));
+
+        data[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        let result = Ncmdump::from_reader(Cursor::new(data));
+        assert!(matches!(
+            result,
+            Err(Errors::LimitExceeded {
+                section: Section::Key,
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_dump_malformed_no_panic() -> Result<()> {
+        let data = std::fs::read("res/test.ncm")?;
+        for length in (0..800).chain([39681, 39682]) {
+            let result = Ncmdump::from_reader(Cursor::new(data[..length].to_vec()));
+            if let Ok(mut ncm) = result {
+                let _ = ncm.get_info();
+                let _ = ncm.get_image();
+                let _ = ncm.get_data();
+            }
+        }
+
+        // The information area is too small
+        let mut data = data[..142].to_vec();
+        data.extend_from_slice(&[4, 0, 0, 0, 0x63, 0x63, 0x63, 0x63]);
+        data.extend_from_slice(&[0; 13]);
+        let mut ncm = Ncmdump::from_reader(Cursor::new(data))?;
+        assert!(matches!(ncm.get_info(), Err(Errors::InvalidInfoLength)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ncmdump_seek_ok() -> Result<()> {
+        let reader = File::open("res/test.ncm")?;
+        let mut ncm = Ncmdump::from_reader(reader)?;
+        assert_eq!(ncm.seek(SeekFrom::Start(4))?, 4);
+        assert_eq!(ncm.seek(SeekFrom::Current(-4))?, 0);
+        assert_eq!(ncm.seek(SeekFrom::End(-16))?, 61424);
+        assert!(ncm.seek(SeekFrom::End(-61441)).is_err());
+        assert!(ncm.seek(SeekFrom::Current(i64::MIN)).is_err());
+        assert!(ncm.seek(SeekFrom::Start(u64::MAX)).is_err());
+        assert_eq!(ncm.stream_position()?, 61424);
+
+        let mut buf = [0; 4];
+        ncm.seek(SeekFrom::Start(0))?;
+        ncm.read_exact(&mut buf)?;
+        assert_eq!(buf, [0x66, 0x4c, 0x61, 0x43]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_ok() -> Result<()> {
+        let reader = File::open("res/test.ncm")?;
+        let mut ncm = Ncmdump::from_reader(reader)?;
+        ncm.verify()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_mismatch_err() -> Result<()> {
+        let mut data = std::fs::read("res/test.ncm")?;
+        // Modify one byte of the information area.
+        data[200] ^= 0xFF;
+        let mut ncm = Ncmdump::from_reader(Cursor::new(data))?;
+        let result = ncm.verify();
+        assert!(matches!(
+            result,
+            Err(Errors::ChecksumMismatch {
+                expected: 0xCDF50220,
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_comment_ok() -> Result<()> {
+        let reader = File::open("res/test.ncm")?;
+        let mut ncm = Ncmdump::from_reader(reader)?;
+        let comment = ncm.get_comment()?;
+
+        assert_eq!(comment.len(), 514);
+        assert!(comment.starts_with("163 key(Don't modify):L64FU3W4YxX3ZFTmbZ+8/"));
+        assert!(comment.ends_with("="));
+        Ok(())
+    }
+
     #[test]
     fn test_ncm_info_convert_ok() {
         let info = NcmInfo::from(RawNcmInfo {
             name: "".to_string(),
-            id: NcmId::String(String::from("")),
+            id: Some(NcmId::String(String::from(""))),
             album: "".to_string(),
+            album_id: None,
             artist: vec![],
-            bitrate: NcmId::String(String::from("")),
-            duration: NcmId::String(String::from("")),
+            bitrate: Some(NcmId::String(String::from(""))),
+            duration: Some(NcmId::String(String::from(""))),
             format: "".to_string(),
             mv_id: None,
             alias: None,
+            trans_names: None,
         });
-        assert_eq!(info.id, 0);
+        assert_eq!(info.id, None);
         assert_eq!(info.artist, Vec::new());
-        assert_eq!(info.bitrate, 0);
-        assert_eq!(info.duration, 0);
+        assert_eq!(info.bitrate, None);
+        assert_eq!(info.duration, None);
+    }
+
+    #[test]
+    fn test_ncm_info_strict_convert_ok() -> Result<()> {
+        let info = NcmInfo::try_from(&RawNcmInfo {
+            name: "".to_string(),
+            id: Some(NcmId::Integer(0)),
+            album: "".to_string(),
+            album_id: None,
+            artist: vec![("".to_string(), NcmId::String(String::from("")))],
+            bitrate: Some(NcmId::String(String::from("320000"))),
+            duration: Some(NcmId::String(String::from(""))),
+            format: "".to_string(),
+            mv_id: Some(NcmId::String(String::from(""))),
+            alias: None,
+            trans_names: None,
+        })?;
+        assert_eq!(info.id, Some(0));
+        assert_eq!(info.artist, vec![(String::new(), None)]);
+        assert_eq!(info.bitrate, Some(320000));
+        assert_eq!(info.duration, None);
+        assert_eq!(info.mv_id, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ncm_info_strict_convert_err() {
+        let raw_info = RawNcmInfo {
+            name: "".to_string(),
+            id: Some(NcmId::Integer(1)),
+            album: "".to_string(),
+            album_id: None,
+            artist: vec![
+                ("".to_string(), NcmId::Integer(1)),
+                ("".to_string(), NcmId::String(String::from("abc"))),
+            ],
+            bitrate: Some(NcmId::String(String::from(""))),
+            duration: Some(NcmId::String(String::from(""))),
+            format: "".to_string(),
+            mv_id: None,
+            alias: None,
+            trans_names: None,
+        };
+        let result = NcmInfo::try_from(&raw_info);
+        assert!(matches!(
+            result,
+            Err(Errors::InvalidField { field, .. }) if field == "artist[1]"
+        ));
+
+        let info = NcmInfo::from(raw_info);
+        assert_eq!(info.artist[1].1, None);
     }
 
     #[test]
@@ -487,11 +1029,13 @@ pub mod tests {
 }"#,
         )?;
         let info = NcmInfo::from(raw);
-        assert_eq!(info.id, 1958557540);
-        assert_eq!(info.artist, vec!((String::from("JVKE"), 32988392),));
-        assert_eq!(info.bitrate, 320000);
-        assert_eq!(info.duration, 209259);
+        assert_eq!(info.id, Some(1958557540));
+        assert_eq!(info.artist, vec!((String::from("JVKE"), Some(32988392)),));
+        assert_eq!(info.bitrate, Some(320000));
+        assert_eq!(info.duration, Some(209259));
         assert_eq!(info.mv_id, Some(14570752));
+        assert_eq!(info.album_id, Some(152231212));
+        assert_eq!(info.trans_names, Some(Vec::new()));
         Ok(())
     }
 
@@ -529,17 +1073,18 @@ pub mod tests {
 }"#,
         )?;
         let info = NcmInfo::from(raw);
-        assert_eq!(info.id, 2062898402);
+        assert_eq!(info.id, Some(2062898402));
         assert_eq!(
             info.artist,
             vec!(
-                (String::from("Alan Walker"), 1045123),
-                (String::from("Zak Abel"), 1053190)
+                (String::from("Alan Walker"), Some(1045123)),
+                (String::from("Zak Abel"), Some(1053190))
             )
         );
-        assert_eq!(info.bitrate, 320000);
-        assert_eq!(info.duration, 187040);
+        assert_eq!(info.bitrate, Some(320000));
+        assert_eq!(info.duration, Some(187040));
         assert_eq!(info.mv_id, Some(14648342));
+        assert_eq!(info.album_id, Some(178429151));
         Ok(())
     }
 
@@ -574,14 +1119,33 @@ pub mod tests {
 }"#,
         )?;
         let info = NcmInfo::from(raw);
-        assert_eq!(info.id, 2056228066);
-        assert_eq!(info.artist, vec!((String::from("AnRain"), 35516167)));
-        assert_eq!(info.bitrate, 320000);
-        assert_eq!(info.duration, 186741);
+        assert_eq!(info.id, Some(2056228066));
+        assert_eq!(info.artist, vec!((String::from("AnRain"), Some(35516167))));
+        assert_eq!(info.bitrate, Some(320000));
+        assert_eq!(info.duration, Some(186741));
         assert_eq!(info.mv_id, None);
         Ok(())
     }
 
+    #[test]
+    fn test_ncm_info_deserialize_missing_ok() -> Result<()> {
+        let raw = serde_json::from_slice::<RawNcmInfo>(
+            br#"{
+   "musicName": "Eternal regret",
+   "artist": [],
+   "album": "Eternal regret",
+   "format": "mp3"
+}"#,
+        )?;
+        assert_eq!(raw.id, None);
+        let info = NcmInfo::try_from(&raw)?;
+        assert_eq!(info.id, None);
+        assert_eq!(info.bitrate, None);
+        assert_eq!(info.duration, None);
+        assert_eq!(NcmInfo::from(raw), info);
+        Ok(())
+    }
+
     #[test]
     fn test_get_image_ok() -> Result<()> {
         let reader = File::open("res/test.ncm")?;
@@ -633,12 +1197,153 @@ pub mod tests {
         Ok(())
     }
 
+    /// The reader which fails after the limit is reached.
+    struct FailingReader {
+        inner: Cursor<Vec<u8>>,
+        limit: u64,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.inner.position() >= self.limit {
+                return Err(std::io::Error::other("broken device"));
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for FailingReader {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
     #[test]
-    fn test_encrypt_ok() -> Result<()> {
+    fn test_copy_to_ok() -> Result<()> {
         let reader = File::open("res/test.ncm")?;
         let mut ncm = Ncmdump::from_reader(reader)?;
+        let mut data = Vec::new();
+        let size = ncm.copy_to(&mut data)?;
+        assert_eq!(size, 61440);
+        assert_eq!(data[..4], [0x66, 0x4c, 0x61, 0x43]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_to_read_err() -> Result<()> {
+        let inner = Cursor::new(std::fs::read("res/test.ncm")?);
+        let reader = FailingReader {
+            inner,
+            limit: 50000,
+        };
+        let mut ncm = Ncmdump::from_reader(reader)?;
+        let mut data = Vec::new();
+        let err = ncm.copy_to(&mut data).unwrap_err();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::Other));
+        assert!(ncm.get_data().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_to_write_err() -> Result<()> {
+        let reader = File::open("res/test.ncm")?;
+        let mut ncm = Ncmdump::from_reader(reader)?;
+        let mut buffer = [0; 1024];
+        let mut writer = &mut buffer[..];
+        let err = ncm.copy_to(&mut writer).unwrap_err();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::WriteZero));
+        Ok(())
+    }
+
+    #[test]
+    fn test_salvage_complete_ok() -> Result<()> {
+        let reader = File::open("res/test.ncm")?;
+        let (mut ncm, report) = Ncmdump::salvage(reader)?;
+        assert_eq!(report.truncated, None);
+        assert_eq!(report.audio_length, 61440);
+        assert!(report
+            .missing_audio_length()
+            .is_some_and(|length| length > 0));
+        assert!(!report.is_missing(Section::Image));
+        assert!(report.is_missing(Section::Audio));
+        assert_eq!(ncm.get_data()?.len(), 61440);
+        Ok(())
+    }
+
+    #[test]
+    fn test_salvage_audio_tail_ok() -> Result<()> {
+        let mut data = std::fs::read("res/test.ncm")?;
+        data.truncate(50000);
+        assert!(Ncmdump::from_reader(Cursor::new(data.clone()))?
+            .get_data()
+            .is_ok());
+
+        let (mut ncm, report) = Ncmdump::salvage(Cursor::new(data))?;
+        assert_eq!(report.truncated, None);
+        assert_eq!(report.audio_length, 50000 - 39682);
+        let music = ncm.get_data()?;
+        assert_eq!(music.len(), 50000 - 39682);
+        assert_eq!(music[..4], [0x66, 0x4c, 0x61, 0x43]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_salvage_truncated_ok() -> Result<()> {
+        let data = std::fs::read("res/test.ncm")?;
+        for (length, section) in [
+            (300, Section::Info),
+            (662, Section::Checksum),
+            (667, Section::Cover),
+            (20000, Section::Image),
+            (39500, Section::Image),
+        ] {
+            assert!(Ncmdump::from_reader(Cursor::new(data[..length].to_vec())).is_err());
+
+            let (mut ncm, report) = Ncmdump::salvage(Cursor::new(data[..length].to_vec()))?;
+            assert_eq!(report.truncated, Some(section), "length {}", length);
+            assert_eq!(report.audio_length, 0);
+            assert_eq!(ncm.layout().audio.offset, length as u64);
+            assert!(ncm.get_data()?.is_empty());
+            assert_eq!(ncm.get_info().is_ok(), section > Section::Info);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_salvage_err() -> Result<()> {
+        let data = std::fs::read("res/test.ncm")?;
+        let Err(err) = Ncmdump::salvage(Cursor::new(data[..100].to_vec())) else {
+            panic!("The key is truncated");
+        };
+        assert_eq!(err.section(), Some(Section::Key));
+        assert!(Ncmdump::salvage(Cursor::new(b"ID3".to_vec())).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_header_ok() -> Result<()> {
+        let reader = File::open("res/test.ncm")?;
+        let mut ncm = Ncmdump::from_reader(reader)?;
+        let header = ncm.shared_header();
+        let mut other = Ncmdump::with_header(header.clone(), File::open("res/test.ncm")?)?;
+        assert!(Arc::ptr_eq(&header, &other.shared_header()));
+        assert_eq!(Arc::strong_count(&header), 3);
+        assert_eq!(other.layout(), ncm.layout());
+        assert_eq!(other.get_info()?, ncm.get_info()?);
+
+        other.seek(SeekFrom::Start(0x1000))?;
+        let mut buf = [0; 16];
+        other.read_exact(&mut buf)?;
+        assert_eq!(buf[..], ncm.get_data()?[0x1000..0x1010]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_ok() -> Result<()> {
+        let reader = File::open("res/test.ncm")?;
+        let ncm = Ncmdump::from_reader(reader)?;
         let mut data = [63, 246, 41, 107];
-        ncm.encrypt(0, &mut data);
+        ncm.header().decrypt(0, &mut data);
         assert_eq!(data, [102, 76, 97, 67]);
         Ok(())
     }
@@ -655,6 +1360,19 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_between_header_ok() -> Result<()> {
+        let expected = Ncmdump::from_reader(File::open("res/test.ncm")?)?.get_data()?;
+        let mut ncm = Ncmdump::from_reader(File::open("res/test.ncm")?)?;
+        let mut data = vec![0; 1000];
+        ncm.read_exact(&mut data)?;
+        ncm.verify()?;
+        assert!(!ncm.get_image()?.is_empty());
+        ncm.read_to_end(&mut data)?;
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
     #[test]
     fn test_ncmdump_multi_read_ok() -> Result<()> {
         let reader = File::open("res/test.ncm")?;
@@ -670,106 +1388,4 @@ pub mod tests {
         assert_eq!(buf, [0x00, 0x00, 0x00, 0x22]);
         Ok(())
     }
-
-    #[test]
-    fn test_decrypt() {
-        let key = [
-            0x23, 0x31, 0x34, 0x6C, 0x6A, 0x6B, 0x5F, 0x21, 0x5C, 0x5D, 0x26, 0x30, 0x55, 0x3C,
-            0x27, 0x28,
-        ];
-        let source = [
-            0x2F, 0xAE, 0x05, 0x53, 0x75, 0xB8, 0x63, 0x15, 0xF7, 0x64, 0x54, 0xE6, 0x6D, 0x9F,
-            0xBC, 0xFD, 0xD4, 0xC3, 0xE5, 0x4A, 0x5D, 0x7D, 0x61, 0x47, 0x93, 0x82, 0x00, 0xAC,
-            0x48, 0xE4, 0x2C, 0x96, 0x43, 0x0F, 0x53, 0x54, 0xBE, 0x36, 0xC8, 0x6D, 0xE5, 0x46,
-            0x47, 0x7E, 0x87, 0x88, 0xBC, 0xF6, 0x0F, 0x97, 0x0F, 0xA9, 0x60, 0xDB, 0xF7, 0x4C,
-            0xD1, 0xD0, 0xD4, 0x44, 0xEC, 0xF6, 0x89, 0x44, 0x6B, 0x88, 0xD3, 0x2A, 0x39, 0x25,
-            0xB8, 0x09, 0x71, 0xA9, 0x3F, 0xE0, 0xC1, 0x07, 0x22, 0x3A, 0xB6, 0x0B, 0xF0, 0xA1,
-            0xB3, 0x26, 0x96, 0x62, 0xBD, 0x30, 0x76, 0xB5, 0xDA, 0x03, 0x6B, 0xB0, 0x99, 0x43,
-            0x2E, 0x7B, 0x8F, 0xD4, 0xD2, 0x9E, 0xFF, 0x37, 0x24, 0xB5, 0x25, 0x56, 0x6A, 0x0B,
-            0x2B, 0x41, 0x19, 0x52, 0xF6, 0xC8, 0x6E, 0x56, 0xE8, 0xC4, 0xF0, 0xCA, 0xFC, 0x9F,
-            0x09, 0xA4, 0xED, 0x51, 0x94, 0x7E, 0xDD, 0xE4, 0xF1, 0x3D, 0x63, 0xEB, 0x7A, 0xBB,
-            0x8C, 0xE6, 0x8F, 0x42, 0xDA, 0xE1, 0x7F, 0x17, 0xB5, 0x06, 0xC8, 0x34, 0x1C, 0xD7,
-            0x4C, 0x7F, 0xBE, 0x7B, 0xE8, 0x66, 0xB1, 0x0C, 0xB6, 0x57, 0x65, 0x78, 0xCF, 0xD0,
-            0xBA, 0xCC, 0x78, 0x09, 0xC1, 0x0F, 0x50, 0xDE, 0x1A, 0x2A, 0x27, 0x5D, 0x83, 0x12,
-            0xBB, 0x3A, 0xA5, 0x12, 0xFB, 0x54, 0xA2, 0xC9, 0x4D, 0x15, 0x46, 0x9F, 0x36, 0x8D,
-            0xF6, 0x79, 0x98, 0xA7, 0x34, 0x7F, 0x84, 0x6D, 0xD9, 0xC7, 0x3C, 0x51, 0xA6, 0x8B,
-            0x95, 0x42, 0x09, 0x13, 0xA9, 0xAE, 0x2C, 0xE4, 0x5F, 0x8A, 0x26, 0x67, 0x3B, 0xF5,
-            0x01, 0x36, 0x0D, 0x20, 0xD0, 0x2C, 0xDE, 0xA1, 0xE2, 0x03, 0x62, 0xD3, 0xB8, 0x9F,
-            0x65, 0xE8, 0xD7, 0xC1, 0x05, 0x88, 0x83, 0x68, 0x50, 0xDB, 0xC7, 0x0F, 0xE5, 0x79,
-            0xF7, 0x3F, 0x37, 0x4A, 0xF4, 0xD9, 0x82, 0xB1, 0xB3, 0x04, 0x0B, 0xB0, 0xD8, 0x5C,
-            0xFA, 0x03, 0x9C, 0x63, 0xFF, 0xCA, 0xE2, 0xE4, 0x57, 0x08, 0x2A, 0x05, 0x34, 0x16,
-            0x03, 0xED, 0xC7, 0x85, 0xE0, 0x3C, 0x43, 0x53, 0x78, 0x8C, 0x88, 0x7F, 0x52, 0xE6,
-            0x94, 0x5D, 0xC7, 0x9A, 0x21, 0xD0, 0xEA, 0x89, 0x7B, 0x09, 0xB0, 0xDA, 0xA2, 0x0B,
-            0xEE, 0xC2, 0x66, 0x04, 0x2A, 0xA0, 0x9C, 0x28, 0xDC, 0xA9, 0xB4, 0x71, 0x90, 0xC8,
-            0x9B, 0x00, 0x7B, 0xF5, 0x7A, 0x9C, 0xAF, 0x2B, 0x8A, 0x7B, 0x0F, 0x70, 0x7F, 0x44,
-            0x01, 0x5A, 0xDB, 0x6D, 0x8E, 0x98, 0x3D, 0x4E, 0x14, 0x71, 0xC1, 0xB2, 0x0F, 0x66,
-            0x8F, 0x14, 0x0A, 0x5C, 0x78, 0xE9, 0x16, 0xD2, 0x45, 0x4F, 0x1F, 0xE3, 0x3F, 0x0E,
-            0xBD, 0x5D, 0x40, 0x75,
-        ];
-        let target = [
-            0x6D, 0x75, 0x73, 0x69, 0x63, 0x3A, 0x7B, 0x22, 0x6D, 0x75, 0x73, 0x69, 0x63, 0x49,
-            0x64, 0x22, 0x3A, 0x31, 0x33, 0x30, 0x35, 0x33, 0x36, 0x36, 0x35, 0x35, 0x36, 0x2C,
-            0x22, 0x6D, 0x75, 0x73, 0x69, 0x63, 0x4E, 0x61, 0x6D, 0x65, 0x22, 0x3A, 0x22, 0xE5,
-            0xAF, 0x92, 0xE9, 0xB8, 0xA6, 0xE5, 0xB0, 0x91, 0xE5, 0xB9, 0xB4, 0x22, 0x2C, 0x22,
-            0x61, 0x6C, 0x69, 0x61, 0x73, 0x22, 0x3A, 0x5B, 0x22, 0xE7, 0x94, 0xB5, 0xE8, 0xA7,
-            0x86, 0xE5, 0x89, 0xA7, 0xE3, 0x80, 0x8A, 0xE6, 0x96, 0x97, 0xE7, 0xA0, 0xB4, 0xE8,
-            0x8B, 0x8D, 0xE7, 0xA9, 0xB9, 0xE3, 0x80, 0x8B, 0xE4, 0xB8, 0xBB, 0xE9, 0xA2, 0x98,
-            0xE6, 0x9B, 0xB2, 0x22, 0x5D, 0x2C, 0x22, 0x61, 0x72, 0x74, 0x69, 0x73, 0x74, 0x22,
-            0x3A, 0x5B, 0x5B, 0x22, 0xE5, 0x8D, 0x8E, 0xE6, 0x99, 0xA8, 0xE5, 0xAE, 0x87, 0x22,
-            0x2C, 0x38, 0x36, 0x31, 0x37, 0x37, 0x37, 0x5D, 0x5D, 0x2C, 0x22, 0x61, 0x6C, 0x62,
-            0x75, 0x6D, 0x49, 0x64, 0x22, 0x3A, 0x37, 0x32, 0x37, 0x30, 0x36, 0x37, 0x38, 0x38,
-            0x2C, 0x22, 0x61, 0x6C, 0x62, 0x75, 0x6D, 0x22, 0x3A, 0x22, 0xE5, 0xAF, 0x92, 0xE9,
-            0xB8, 0xA6, 0xE5, 0xB0, 0x91, 0xE5, 0xB9, 0xB4, 0x22, 0x2C, 0x22, 0x61, 0x6C, 0x62,
-            0x75, 0x6D, 0x50, 0x69, 0x63, 0x44, 0x6F, 0x63, 0x49, 0x64, 0x22, 0x3A, 0x31, 0x30,
-            0x39, 0x39, 0x35, 0x31, 0x31, 0x36, 0x33, 0x35, 0x32, 0x30, 0x36, 0x33, 0x38, 0x35,
-            0x32, 0x32, 0x2C, 0x22, 0x61, 0x6C, 0x62, 0x75, 0x6D, 0x50, 0x69, 0x63, 0x22, 0x3A,
-            0x22, 0x68, 0x74, 0x74, 0x70, 0x3A, 0x2F, 0x2F, 0x70, 0x33, 0x2E, 0x6D, 0x75, 0x73,
-            0x69, 0x63, 0x2E, 0x31, 0x32, 0x36, 0x2E, 0x6E, 0x65, 0x74, 0x2F, 0x71, 0x52, 0x51,
-            0x54, 0x53, 0x5F, 0x54, 0x72, 0x6F, 0x5A, 0x6F, 0x39, 0x53, 0x4C, 0x56, 0x35, 0x79,
-            0x71, 0x70, 0x54, 0x35, 0x41, 0x3D, 0x3D, 0x2F, 0x31, 0x30, 0x39, 0x39, 0x35, 0x31,
-            0x31, 0x36, 0x33, 0x35, 0x32, 0x30, 0x36, 0x33, 0x38, 0x35, 0x32, 0x32, 0x2E, 0x6A,
-            0x70, 0x67, 0x22, 0x2C, 0x22, 0x6D, 0x76, 0x49, 0x64, 0x22, 0x3A, 0x30, 0x2C, 0x22,
-            0x62, 0x69, 0x74, 0x72, 0x61, 0x74, 0x65, 0x22, 0x3A, 0x39, 0x32, 0x33, 0x33, 0x37,
-            0x38, 0x2C, 0x22, 0x64, 0x75, 0x72, 0x61, 0x74, 0x69, 0x6F, 0x6E, 0x22, 0x3A, 0x33,
-            0x31, 0x35, 0x31, 0x34, 0x36, 0x2C, 0x22, 0x66, 0x6F, 0x72, 0x6D, 0x61, 0x74, 0x22,
-            0x3A, 0x22, 0x66, 0x6C, 0x61, 0x63, 0x22, 0x7D,
-        ];
-        let result = Ncmdump::<File>::decrypt(&source, &key).unwrap();
-        assert_eq!(&result[..], &target);
-    }
-
-    #[test]
-    fn test_build_key_box() {
-        let key = [
-            0x31, 0x31, 0x38, 0x31, 0x39, 0x38, 0x30, 0x33, 0x33, 0x32, 0x38, 0x35, 0x45, 0x37,
-            0x66, 0x54, 0x34, 0x39, 0x78, 0x37, 0x64, 0x6F, 0x66, 0x39, 0x4F, 0x4B, 0x43, 0x67,
-            0x67, 0x39, 0x63, 0x64, 0x76, 0x68, 0x45, 0x75, 0x65, 0x7A, 0x79, 0x33, 0x69, 0x5A,
-            0x43, 0x4C, 0x31, 0x6E, 0x46, 0x76, 0x42, 0x46, 0x64, 0x31, 0x54, 0x34, 0x75, 0x53,
-            0x6B, 0x74, 0x41, 0x4A, 0x4B, 0x6D, 0x77, 0x5A, 0x58, 0x73, 0x69, 0x6A, 0x50, 0x62,
-            0x69, 0x6A, 0x6C, 0x69, 0x69, 0x6F, 0x6E, 0x56, 0x55, 0x58, 0x58, 0x67, 0x39, 0x70,
-            0x6C, 0x54, 0x62, 0x58, 0x45, 0x63, 0x6C, 0x41, 0x45, 0x39, 0x4C, 0x62,
-        ];
-        let key_box = [
-            0x43, 0x63, 0x9D, 0xE2, 0x5B, 0x4B, 0x55, 0xBB, 0x4C, 0xCF, 0x2A, 0x62, 0x0E, 0x48,
-            0x8A, 0x15, 0x59, 0x52, 0xBA, 0x6C, 0xEF, 0x6D, 0x72, 0x39, 0xA0, 0x9A, 0xA9, 0x27,
-            0x66, 0xBC, 0xF9, 0xC0, 0x47, 0xDF, 0x7D, 0xDE, 0x3B, 0x81, 0x04, 0xFF, 0x90, 0x77,
-            0x80, 0x50, 0x54, 0xBD, 0x0D, 0x58, 0x34, 0x0A, 0x44, 0xA8, 0x5F, 0x99, 0xC6, 0xBE,
-            0x4E, 0x4D, 0x13, 0x17, 0x83, 0x01, 0x35, 0x5C, 0xF4, 0x7B, 0x53, 0x31, 0x86, 0xD4,
-            0xB8, 0xAB, 0xD1, 0xB5, 0x68, 0xDC, 0x96, 0xF1, 0x9C, 0xE8, 0x7A, 0x1B, 0xB0, 0x56,
-            0x22, 0x1A, 0x51, 0x92, 0xBF, 0xFA, 0xB1, 0x19, 0x88, 0x26, 0x49, 0x08, 0xEB, 0xAC,
-            0x14, 0x28, 0xAD, 0x3A, 0x8C, 0x85, 0x84, 0x2C, 0x82, 0xB3, 0xA6, 0xA2, 0xA3, 0x12,
-            0x78, 0xA1, 0x57, 0xAE, 0x00, 0x2F, 0xB6, 0x61, 0xA5, 0x6F, 0x5A, 0x89, 0x29, 0x46,
-            0x2E, 0x4F, 0x36, 0x40, 0x07, 0x87, 0xA7, 0x65, 0x73, 0xC4, 0x7C, 0x33, 0x1E, 0xE5,
-            0x10, 0xB4, 0xFD, 0xC9, 0xE0, 0xB7, 0x97, 0x32, 0x5D, 0x64, 0x41, 0xF0, 0x20, 0xC3,
-            0x95, 0xFE, 0xD2, 0x21, 0xFB, 0x75, 0x3D, 0x0B, 0x3E, 0xF2, 0xD5, 0xCB, 0xD6, 0xF7,
-            0x1F, 0x24, 0x45, 0x69, 0xB9, 0xDA, 0x6A, 0x76, 0x03, 0xF8, 0x70, 0x8E, 0xC1, 0xC8,
-            0xD7, 0x4A, 0xD0, 0x9E, 0xCD, 0xA4, 0xCE, 0xAA, 0x1D, 0xED, 0xF6, 0x02, 0x60, 0xE3,
-            0xDB, 0x8D, 0x09, 0xF3, 0x37, 0xE1, 0xC5, 0xCA, 0x8F, 0x2D, 0x7F, 0x74, 0x42, 0x6E,
-            0x8B, 0x3F, 0x23, 0xC2, 0xD3, 0xCC, 0xD9, 0xEE, 0x98, 0xE6, 0x11, 0x05, 0xEA, 0xD8,
-            0xB2, 0xE4, 0xF5, 0xE7, 0x71, 0x2B, 0x93, 0x9B, 0x3C, 0x30, 0xE9, 0xC7, 0x38, 0xEC,
-            0x18, 0x6B, 0x79, 0xFC, 0xAF, 0x5E, 0x9F, 0x7E, 0x91, 0xDD, 0x16, 0x94, 0x0F, 0x06,
-            0x67, 0x25, 0x0C, 0x1C,
-        ];
-        assert_eq!(Ncmdump::<File>::build_key_box(&key), key_box);
-    }
 }
@@ -0,0 +1,137 @@
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::{Errors, Result};
+
+/// The observer of the conversion progress.
+pub trait Progress {
+    /// It's called after every chunk is converted.
+    ///
+    /// The `done` is the bytes of audio data which has been converted,
+    /// and the `total` is the length of the audio data.
+    fn update(&self, done: u64, total: u64);
+}
+
+impl<F> Progress for F
+where
+    F: Fn(u64, u64),
+{
+    fn update(&self, done: u64, total: u64) {
+        self(done, total)
+    }
+}
+
+/// The token to cancel the conversion.
+///
+/// It can be cloned and shared between threads, the conversion is cancelled
+/// between chunks with [`Errors::Cancelled`].
+///
+/// # Example
+///
+/// ```rust
+/// # use ncmdump::CancelToken;
+/// #
+/// let token = CancelToken::new();
+/// let other = token.clone();
+/// other.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Create a new token which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the conversions which use this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Check if the token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Copy the data from reader to writer by chunks, and report the progress after every chunk.
+///
+/// The `done` is start from the `start`, and the cancellation is checked before every chunk.
+pub(crate) fn copy<R, W, P>(
+    reader: &mut R,
+    writer: &mut W,
+    start: u64,
+    total: u64,
+    progress: &P,
+    cancel: &CancelToken,
+) -> Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+    P: Progress + ?Sized,
+{
+    let mut buffer = [0; 0x8000];
+    let mut written = 0;
+    loop {
+        if cancel.is_cancelled() {
+            return Err(Errors::Cancelled);
+        }
+        let size = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(size) => size,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        writer.write_all(&buffer[..size])?;
+        written += size as u64;
+        progress.update(start + written, total);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::Cursor;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn test_copy_progress_ok() -> Result<()> {
+        let mut reader = Cursor::new(vec![0; 0x10001]);
+        let mut output = Vec::new();
+        let updates = RefCell::new(Vec::new());
+        let progress = |done, total| updates.borrow_mut().push((done, total));
+        let size = copy(
+            &mut reader,
+            &mut output,
+            0,
+            0x10001,
+            &progress,
+            &CancelToken::new(),
+        )?;
+        assert_eq!(size, 0x10001);
+        assert_eq!(output.len(), 0x10001);
+        assert_eq!(
+            updates.into_inner(),
+            vec![(0x8000, 0x10001), (0x10000, 0x10001), (0x10001, 0x10001)],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_cancelled_err() {
+        let mut reader = Cursor::new(vec![0; 0x10000]);
+        let mut output = Vec::new();
+        let token = CancelToken::new();
+        let progress = |_, _| token.cancel();
+        let result = copy(&mut reader, &mut output, 0, 0x10000, &progress, &token);
+        assert!(matches!(result, Err(Errors::Cancelled)));
+        assert_eq!(output.len(), 0x8000);
+    }
+}
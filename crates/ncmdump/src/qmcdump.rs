@@ -1,8 +1,11 @@
+#[cfg(feature = "std")]
 use std::io::{Read, Seek, SeekFrom, Write};
 
+#[cfg(feature = "std")]
 use crate::error::Result;
+#[cfg(feature = "std")]
+use crate::progress::{self, CancelToken, Progress};
 
-const BUFFER_SIZE: usize = 8192;
 const KEY: [u8; 256] = [
     0x77, 0x48, 0x32, 0x73, 0xDE, 0xF2, 0xC0, 0xC8, 0x95, 0xEC, 0x30, 0xB2, 0x51, 0xC3, 0xE1, 0xA0,
     0x9E, 0xE6, 0x9D, 0xCF, 0xFA, 0x7F, 0x14, 0xD1, 0xCE, 0xB8, 0xDC, 0xC3, 0x4A, 0x67, 0x93, 0xD6,
@@ -22,19 +25,10 @@ const KEY: [u8; 256] = [
     0x1C, 0x71, 0xDB, 0x00, 0xBC, 0xFD, 0x0C, 0x6C, 0xA5, 0x47, 0xF7, 0xF6, 0x00, 0x79, 0x4A, 0x11,
 ];
 
-/// The qmc file dump wrapper.
-pub struct QmcDump<S>
-where
-    S: Read,
-{
-    reader: S,
-    cursor: u64,
-}
+/// The qmc cipher, it works on byte slices without any reader.
+pub struct QmcCipher;
 
-impl<S> QmcDump<S>
-where
-    S: Read,
-{
+impl QmcCipher {
     fn map_l(value: u64) -> u8 {
         let v = if value > 0x7FFF {
             value % 0x7FFF
@@ -45,12 +39,39 @@ where
         KEY[index]
     }
 
-    fn encrypt(offset: u64, buffer: &mut [u8]) {
+    /// Decrypt the data in place, the offset is relative to the start of file.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use ncmdump::QmcCipher;
+    /// #
+    /// let mut data = [0xA5, 0x06, 0xB7, 0x89];
+    /// QmcCipher::decrypt(0, &mut data);
+    /// assert_eq!(data, *b"fLaC");
+    /// ```
+    pub fn decrypt(offset: u64, buffer: &mut [u8]) {
         for (index, byte) in buffer.iter_mut().enumerate() {
             *byte ^= Self::map_l(offset + index as u64);
         }
     }
+}
+
+/// The qmc file dump wrapper.
+#[cfg(feature = "std")]
+pub struct QmcDump<S>
+where
+    S: Read,
+{
+    reader: S,
+    cursor: u64,
+}
 
+#[cfg(feature = "std")]
+impl<S> QmcDump<S>
+where
+    S: Read,
+{
     /// Create QmcDump from reader.
     ///
     /// # Example
@@ -92,31 +113,95 @@ where
     ///     Ok(())
     /// }
     /// ```
-    pub fn get_data(&mut self) -> std::io::Result<Vec<u8>> {
-        let mut buffer = [0; BUFFER_SIZE];
+    pub fn get_data(&mut self) -> Result<Vec<u8>> {
         let mut output = Vec::new();
-        while let Ok(size) = self.read(&mut buffer) {
-            if size == 0 {
-                break;
-            }
-            output.write_all(&buffer[..size])?;
-        }
+        self.copy_to(&mut output)?;
         Ok(output)
     }
+
+    /// Decrypt the music data from qmcdump into the writer, and return the size of bytes
+    /// written. Any read or write error is returned, instead of a truncated music.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use std::fs::File;
+    ///
+    /// use anyhow::Result;
+    /// use ncmdump::QmcDump;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let file = File::open("res/test.qmcflac")?;
+    ///     let mut qmc = QmcDump::from_reader(file)?;
+    ///     let mut target = File::create("res/test.flac")?;
+    ///     let _ = qmc.copy_to(&mut target)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn copy_to<W>(&mut self, writer: &mut W) -> Result<u64>
+    where
+        W: Write + ?Sized,
+    {
+        let start = self.cursor;
+        progress::copy(self, writer, start, 0, &|_, _| {}, &CancelToken::new())
+    }
 }
 
+#[cfg(feature = "std")]
+impl<S> QmcDump<S>
+where
+    S: Read + Seek,
+{
+    /// Get the music data from qmcdump, report the progress and check the cancellation
+    /// between chunks.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use std::fs::File;
+    ///
+    /// use anyhow::Result;
+    /// use ncmdump::{CancelToken, QmcDump};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let file = File::open("res/test.qmcflac")?;
+    ///     let mut qmc = QmcDump::from_reader(file)?;
+    ///     let progress = |done, total| println!("{}/{}", done, total);
+    ///     let music = qmc.get_data_with_progress(&progress, &CancelToken::new())?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_data_with_progress<P>(
+        &mut self,
+        progress: &P,
+        cancel: &CancelToken,
+    ) -> Result<Vec<u8>>
+    where
+        P: Progress + ?Sized,
+    {
+        let start = self.cursor;
+        let total = self.reader.seek(SeekFrom::End(0))?;
+        self.reader.seek(SeekFrom::Start(start))?;
+        let mut data = Vec::new();
+        progress::copy(self, &mut data, start, total, progress, cancel)?;
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "std")]
 impl<R> Read for QmcDump<R>
 where
     R: Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let size = self.reader.read(buf)?;
-        Self::encrypt(self.cursor, buf);
+        QmcCipher::decrypt(self.cursor, &mut buf[..size]);
         self.cursor += size as u64;
         Ok(size)
     }
 }
 
+#[cfg(feature = "std")]
 impl<R> Seek for QmcDump<R>
 where
     R: Read + Seek,
@@ -127,7 +212,7 @@ where
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use std::fs::File;
     use std::io::Cursor;
@@ -138,21 +223,21 @@ mod tests {
 
     #[test]
     fn test_qmcdump_map_ok() {
-        let dest = QmcDump::<File>::map_l(0x99);
+        let dest = QmcCipher::map_l(0x99);
         assert_eq!(dest, 146);
 
-        let dest = QmcDump::<File>::map_l(0x8FFF);
+        let dest = QmcCipher::map_l(0x8FFF);
         assert_eq!(dest, 195);
     }
 
     #[test]
     fn test_qmcdump_encrypt_ok() {
         let mut data = [0x00, 0x01, 0x02, 0x03];
-        QmcDump::<File>::encrypt(0, &mut data);
+        QmcCipher::decrypt(0, &mut data);
         assert_eq!(data, [0xC3, 0x4B, 0xD4, 0xC9]);
 
         let mut data = [0x00, 0x01, 0x02, 0x03];
-        QmcDump::<File>::encrypt(0x7fff, &mut data);
+        QmcCipher::decrypt(0x7fff, &mut data);
         assert_eq!(data, [0x4A, 0x4B, 0xD4, 0xC9]);
     }
 
@@ -160,12 +245,12 @@ mod tests {
     fn test_encrypt_head_ok() -> Result<()> {
         // fLaC
         let mut input = [0xA5, 0x06, 0xB7, 0x89];
-        QmcDump::<File>::encrypt(0, &mut input);
+        QmcCipher::decrypt(0, &mut input);
         assert_eq!(input, [0x66, 0x4C, 0x61, 0x43]);
 
         // ID3
         let mut input = [0x8A, 0x0E, 0xE5];
-        QmcDump::<File>::encrypt(0, &mut input);
+        QmcCipher::decrypt(0, &mut input);
         assert_eq!(input, [0x49, 0x44, 0x33]);
         Ok(())
     }
@@ -175,6 +260,7 @@ mod tests {
         let input = File::open("res/test.qmcflac")?;
         let mut output = File::options()
             .create(true)
+            .truncate(true)
             .write(true)
             .open("res/test.flac")?;
         let mut qmc = QmcDump::from_reader(input)?;
@@ -183,6 +269,40 @@ mod tests {
         Ok(())
     }
 
+    /// The reader which fails after the first read.
+    struct FailingReader(bool);
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if std::mem::replace(&mut self.0, true) {
+                return Err(std::io::Error::other("broken device"));
+            }
+            buf.fill(0);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn test_copy_to_ok() -> Result<()> {
+        let input = Cursor::new(vec![0; 0x9000]);
+        let mut qmc = QmcDump::from_reader(input)?;
+        let mut output = Vec::new();
+        let size = qmc.copy_to(&mut output)?;
+        assert_eq!(size, 0x9000);
+        assert_eq!(output[..4], [0xC3, 0x4A, 0xD6, 0xCA]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_to_read_err() -> Result<()> {
+        let mut qmc = QmcDump::from_reader(FailingReader(false))?;
+        let mut output = Vec::new();
+        let err = qmc.copy_to(&mut output).unwrap_err();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::Other));
+        assert!(qmc.get_data().is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_qmcdump_read_ok() -> Result<()> {
         let input = Cursor::new([0x00, 0x01, 0x02, 0x03]);
@@ -0,0 +1,108 @@
+use crate::error::Section;
+use crate::NcmInfo;
+
+/// The report of a salvaged ncm file, see [`Ncmdump::salvage`](crate::Ncmdump::salvage).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SalvageReport {
+    /// The first truncated section, none if the file header is complete
+    pub truncated: Option<Section>,
+    /// The length of the recovered audio data
+    pub audio_length: u64,
+    /// The expected length of the audio data, estimated by the duration and bitrate
+    pub expected_audio_length: Option<u64>,
+}
+
+impl SalvageReport {
+    pub(crate) fn new(
+        truncated: Option<Section>,
+        audio_length: u64,
+        info: Option<&NcmInfo>,
+    ) -> Self {
+        let expected_audio_length = info.and_then(|info| {
+            let duration = info.duration?;
+            let bitrate = info.bitrate?;
+            Some(duration.checked_mul(bitrate)? / 8000)
+        });
+        Self {
+            truncated,
+            audio_length,
+            expected_audio_length,
+        }
+    }
+
+    /// Check if the section is missing or incomplete.
+    ///
+    /// The sections after the truncated one are missing, and the audio data is also
+    /// missing when it's shorter than the expected length.
+    pub fn is_missing(&self, section: Section) -> bool {
+        if self.truncated.is_some_and(|truncated| section >= truncated) {
+            return true;
+        }
+        section == Section::Audio && self.missing_audio_length().is_some_and(|length| length > 0)
+    }
+
+    /// The estimated length of the missing audio data.
+    ///
+    /// It's only a hint, the bitrate of the music is usually variable.
+    pub fn missing_audio_length(&self) -> Option<u64> {
+        self.expected_audio_length
+            .map(|expected| expected.saturating_sub(self.audio_length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn info(duration: Option<u64>, bitrate: Option<u64>) -> NcmInfo {
+        NcmInfo {
+            name: "name".into(),
+            id: None,
+            album: "album".into(),
+            album_id: None,
+            artist: Vec::new(),
+            bitrate,
+            duration,
+            format: "flac".into(),
+            mv_id: None,
+            alias: None,
+            trans_names: None,
+        }
+    }
+
+    #[test]
+    fn test_salvage_report_complete_ok() {
+        let info = info(Some(1000), Some(320000));
+        let report = SalvageReport::new(None, 40000, Some(&info));
+        assert_eq!(report.expected_audio_length, Some(40000));
+        assert_eq!(report.missing_audio_length(), Some(0));
+        assert!(!report.is_missing(Section::Image));
+        assert!(!report.is_missing(Section::Audio));
+    }
+
+    #[test]
+    fn test_salvage_report_audio_missing_ok() {
+        let info = info(Some(1000), Some(320000));
+        let report = SalvageReport::new(None, 30000, Some(&info));
+        assert_eq!(report.missing_audio_length(), Some(10000));
+        assert!(!report.is_missing(Section::Image));
+        assert!(report.is_missing(Section::Audio));
+    }
+
+    #[test]
+    fn test_salvage_report_truncated_ok() {
+        let report = SalvageReport::new(Some(Section::Info), 0, None);
+        assert_eq!(report.expected_audio_length, None);
+        assert_eq!(report.missing_audio_length(), None);
+        assert!(!report.is_missing(Section::Key));
+        assert!(report.is_missing(Section::Info));
+        assert!(report.is_missing(Section::Image));
+        assert!(report.is_missing(Section::Audio));
+
+        let info = info(None, Some(320000));
+        let report = SalvageReport::new(None, 0, Some(&info));
+        assert_eq!(report.expected_audio_length, None);
+    }
+}
@@ -0,0 +1,279 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+
+use super::get_image_mime_type;
+use crate::error::{Errors, Result};
+
+/// The quality of jpeg when the resized cover is re-encoded without a quality.
+const DEFAULT_QUALITY: u8 = 90;
+
+/// The options of processing the cover image.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CoverOptions {
+    /// Embed the cover into the music, default is true
+    pub embed: bool,
+    /// The maximum width and height of the cover, it's scaled down with the aspect
+    /// ratio kept if it's larger. Default is none, the cover isn't resized.
+    pub max_size: Option<u32>,
+    /// Re-encode the cover into jpeg with the quality from 1 to 100. Default is none,
+    /// the cover is kept in its format.
+    pub quality: Option<u8>,
+}
+
+impl Default for CoverOptions {
+    fn default() -> Self {
+        Self {
+            embed: true,
+            max_size: None,
+            quality: None,
+        }
+    }
+}
+
+impl CoverOptions {
+    /// Resize and re-encode the cover image by the options, it's returned as is if
+    /// there is nothing to do.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::fs::File;
+    /// #
+    /// # use anyhow::Result;
+    /// # use ncmdump::tag::CoverOptions;
+    /// # use ncmdump::Ncmdump;
+    /// #
+    /// # fn main() -> Result<()> {
+    /// let image = Ncmdump::from_reader(File::open("res/test.ncm")?)?.get_image()?;
+    /// let options = CoverOptions {
+    ///     max_size: Some(100),
+    ///     quality: Some(80),
+    ///     ..Default::default()
+    /// };
+    /// let cover = options.process(&image)?;
+    /// assert_eq!(cover[..3], [0xFF, 0xD8, 0xFF]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn process(&self, image: &[u8]) -> Result<Vec<u8>> {
+        if image.is_empty() || (self.max_size.is_none() && self.quality.is_none()) {
+            return Ok(image.to_vec());
+        }
+        let decoded = image::load_from_memory(image).map_err(cover_error)?;
+        let (width, height) = decoded.dimensions();
+        let decoded = match self.max_size {
+            Some(size) if width > size || height > size => {
+                decoded.resize(size, size, FilterType::Lanczos3)
+            }
+            // The cover is kept as is if it's small enough and not re-encoded.
+            _ if self.quality.is_none() => return Ok(image.to_vec()),
+            _ => decoded,
+        };
+        match (self.quality, get_image_mime_type(image)) {
+            (None, "image/png") => {
+                let mut output = Cursor::new(Vec::new());
+                decoded
+                    .write_to(&mut output, ImageFormat::Png)
+                    .map_err(cover_error)?;
+                Ok(output.into_inner())
+            }
+            (quality, _) => encode_jpeg(&decoded, quality.unwrap_or(DEFAULT_QUALITY)),
+        }
+    }
+}
+
+/// Convert the cover image into jpeg, it's returned as is if it's already jpeg.
+pub fn to_jpeg(image: &[u8], quality: Option<u8>) -> Result<Vec<u8>> {
+    if get_image_mime_type(image) == "image/jpeg" {
+        return Ok(image.to_vec());
+    }
+    let decoded = image::load_from_memory(image).map_err(cover_error)?;
+    encode_jpeg(&decoded, quality.unwrap_or(DEFAULT_QUALITY))
+}
+
+/// Encode the image into jpeg, the alpha channel is dropped.
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    JpegEncoder::new_with_quality(&mut output, quality.clamp(1, 100))
+        .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))
+        .map_err(cover_error)?;
+    Ok(output)
+}
+
+fn cover_error(source: image::ImageError) -> Errors {
+    Errors::Cover {
+        source: Box::new(source),
+    }
+}
+
+/// The size and color depth of the image, they're written into the flac picture block.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(super) struct ImageInfo {
+    pub(super) width: u32,
+    pub(super) height: u32,
+    /// The bits per pixel
+    pub(super) depth: u32,
+    /// The number of colors of the indexed image, zero if it's not indexed
+    pub(super) colors: u32,
+}
+
+impl ImageInfo {
+    /// Parse the header of png, jpeg, gif and bmp image, it's all zero if the image
+    /// can't be parsed.
+    pub(super) fn parse(image: &[u8]) -> Self {
+        match get_image_mime_type(image) {
+            "image/png" => Self::parse_png(image),
+            "image/jpeg" => Self::parse_jpeg(image),
+            "image/gif" => Self::parse_gif(image),
+            "image/bmp" => Self::parse_bmp(image),
+            _ => None,
+        }
+        .unwrap_or_default()
+    }
+
+    fn parse_png(image: &[u8]) -> Option<Self> {
+        // The IHDR chunk is the first chunk after the signature.
+        let header = image.get(16..26)?;
+        let bits = header[8] as u32;
+        let (depth, colors) = match header[9] {
+            0 => (bits, 0),
+            2 => (bits * 3, 0),
+            3 => (bits, 1 << bits),
+            4 => (bits * 2, 0),
+            6 => (bits * 4, 0),
+            _ => return None,
+        };
+        Some(Self {
+            width: u32::from_be_bytes(header[..4].try_into().ok()?),
+            height: u32::from_be_bytes(header[4..8].try_into().ok()?),
+            depth,
+            colors,
+        })
+    }
+
+    fn parse_jpeg(image: &[u8]) -> Option<Self> {
+        let mut offset = 2;
+        loop {
+            let marker = image.get(offset..offset + 4)?;
+            if marker[0] != 0xFF {
+                return None;
+            }
+            let length = u16::from_be_bytes([marker[2], marker[3]]) as usize;
+            // The start of frame markers, except DHT, JPG and DAC.
+            if matches!(marker[1], 0xC0..=0xCF) && !matches!(marker[1], 0xC4 | 0xC8 | 0xCC) {
+                let frame = image.get(offset + 4..offset + 10)?;
+                return Some(Self {
+                    width: u16::from_be_bytes([frame[3], frame[4]]) as u32,
+                    height: u16::from_be_bytes([frame[1], frame[2]]) as u32,
+                    depth: frame[0] as u32 * frame[5] as u32,
+                    colors: 0,
+                });
+            }
+            offset += 2 + length;
+        }
+    }
+
+    fn parse_gif(image: &[u8]) -> Option<Self> {
+        let header = image.get(6..11)?;
+        let bits = (header[4] & 0x07) as u32 + 1;
+        Some(Self {
+            width: u16::from_le_bytes([header[0], header[1]]) as u32,
+            height: u16::from_le_bytes([header[2], header[3]]) as u32,
+            depth: bits,
+            colors: 1 << bits,
+        })
+    }
+
+    fn parse_bmp(image: &[u8]) -> Option<Self> {
+        let header = image.get(18..30)?;
+        let depth = u16::from_le_bytes([header[10], header[11]]) as u32;
+        Some(Self {
+            width: i32::from_le_bytes(header[..4].try_into().ok()?).unsigned_abs(),
+            height: i32::from_le_bytes(header[4..8].try_into().ok()?).unsigned_abs(),
+            depth,
+            colors: if depth <= 8 { 1 << depth } else { 0 },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    fn build_png(width: u32, height: u32) -> Result<Vec<u8>> {
+        let image = RgbaImage::from_pixel(width, height, Rgba([0x20, 0x40, 0x60, 0xFF]));
+        let mut output = Cursor::new(Vec::new());
+        DynamicImage::ImageRgba8(image).write_to(&mut output, ImageFormat::Png)?;
+        Ok(output.into_inner())
+    }
+
+    #[test]
+    fn test_process_cover_ok() -> Result<()> {
+        let png = build_png(300, 200)?;
+        assert_eq!(CoverOptions::default().process(&png)?, png);
+        assert!(CoverOptions::default().process(&[])?.is_empty());
+
+        let options = CoverOptions {
+            max_size: Some(150),
+            ..Default::default()
+        };
+        let resized = options.process(&png)?;
+        assert_eq!(get_image_mime_type(&resized), "image/png");
+        assert_eq!(image::load_from_memory(&resized)?.dimensions(), (150, 100));
+        let options = CoverOptions {
+            max_size: Some(500),
+            ..Default::default()
+        };
+        assert_eq!(options.process(&png)?, png);
+
+        let options = CoverOptions {
+            quality: Some(80),
+            ..Default::default()
+        };
+        let jpeg = options.process(&png)?;
+        assert_eq!(get_image_mime_type(&jpeg), "image/jpeg");
+        assert_eq!(image::load_from_memory(&jpeg)?.dimensions(), (300, 200));
+        assert_eq!(to_jpeg(&jpeg, None)?, jpeg);
+        assert_eq!(get_image_mime_type(&to_jpeg(&png, None)?), "image/jpeg");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_cover_err() {
+        let options = CoverOptions {
+            quality: Some(80),
+            ..Default::default()
+        };
+        let result = options.process(b"\x89PNG\r\n\x1a\n\0\0\0\0");
+        assert!(matches!(result, Err(Errors::Cover { .. })));
+    }
+
+    #[test]
+    fn test_parse_image_info_ok() -> Result<()> {
+        let png = build_png(300, 200)?;
+        let info = ImageInfo::parse(&png);
+        assert_eq!((info.width, info.height, info.depth), (300, 200, 32));
+
+        let options = CoverOptions {
+            quality: Some(80),
+            ..Default::default()
+        };
+        let info = ImageInfo::parse(&options.process(&png)?);
+        assert_eq!((info.width, info.height, info.depth), (300, 200, 24));
+
+        let gif = b"GIF89a\x10\x00\x20\x00\x91\x00\x00";
+        let info = ImageInfo::parse(gif);
+        assert_eq!(
+            (info.width, info.height, info.depth, info.colors),
+            (16, 32, 2, 4)
+        );
+        assert_eq!(ImageInfo::parse(b"unknown image"), ImageInfo::default());
+        Ok(())
+    }
+}
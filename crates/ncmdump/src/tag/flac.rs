@@ -0,0 +1,224 @@
+use std::io::Cursor;
+
+use metaflac::block::{Picture, PictureType};
+use metaflac::{Block, BlockType};
+
+use super::cover::ImageInfo;
+use super::{
+    collect_fields, get_image_mime_type, get_vorbis_comments, is_extra_comment, Container, Field,
+    Lyrics, TagOptions, TagPolicy, Tags,
+};
+use crate::error::{Errors, Result, Section};
+
+/// Write the vorbis comments into the flac music by the policy of the options.
+///
+/// With the `Keep` policy, the music is left untouched if it already has comments or
+/// pictures. With the default `Overwrite` policy, the mapped comments are replaced and
+/// the padding is removed, and if the extras aren't kept, the embedded lyrics and
+/// ReplayGain comments are removed and the pictures are replaced by the cover. With
+/// the `FillMissing` policy, only the missing comments are written. The other blocks
+/// are kept.
+pub(crate) fn write(data: &[u8], tags: &Tags, options: &TagOptions) -> Result<Vec<u8>> {
+    // The blocks of a truncated file can't be parsed by `metaflac`.
+    if !is_metadata_complete(data) {
+        return Err(Errors::Truncated {
+            section: Section::Audio,
+            offset: data.len() as u64,
+        });
+    }
+    let mut tag =
+        metaflac::Tag::read_from(&mut Cursor::new(data)).unwrap_or_else(|_| metaflac::Tag::new());
+    let tagged = tag
+        .vorbis_comments()
+        .is_some_and(|mc| !mc.comments.is_empty())
+        || tag.pictures().next().is_some();
+    if options.keeps(tagged) {
+        return Ok(data.to_vec());
+    }
+    let mc = tag.vorbis_comments_mut();
+    if options.removes_extras() {
+        mc.comments.retain(|key, _| !is_extra_comment(key));
+    }
+    if let Some(info) = &tags.info {
+        for (key, values) in get_vorbis_comments(info, options) {
+            let embedded = mc.comments.keys().any(|k| k.eq_ignore_ascii_case(&key));
+            if options.writes(embedded, values.concat().as_bytes()) {
+                // The name of comment is case insensitive.
+                mc.comments.retain(|k, _| !k.eq_ignore_ascii_case(&key));
+                mc.set(key, values);
+            }
+        }
+    }
+    if let Some(comment) = tags.comment.as_ref().filter(|_| options.comment) {
+        if options.writes(mc.get("DESCRIPTION").is_some(), comment.as_bytes()) {
+            mc.set("DESCRIPTION", vec![comment.to_string()]);
+        }
+    }
+    let lyrics = mc.comments.keys().any(|key| {
+        key.eq_ignore_ascii_case("LYRICS") || key.eq_ignore_ascii_case("UNSYNCEDLYRICS")
+    });
+    if !tags.lyrics.is_empty() && options.writes_extra(lyrics) {
+        mc.comments
+            .retain(|key, _| !key.eq_ignore_ascii_case("LYRICS"));
+        mc.set("LYRICS", tags.lyrics.iter().map(Lyrics::to_lrc).collect());
+    }
+    if !tags.image.is_empty() && options.writes_extra(tag.pictures().next().is_some()) {
+        let info = ImageInfo::parse(&tags.image);
+        tag.remove_blocks(BlockType::Picture);
+        tag.push_block(Block::Picture(Picture {
+            picture_type: PictureType::CoverFront,
+            mime_type: get_image_mime_type(&tags.image).to_string(),
+            description: String::new(),
+            width: info.width,
+            height: info.height,
+            depth: info.depth,
+            num_colors: info.colors,
+            data: tags.image.to_vec(),
+        }));
+    }
+
+    let audio = metaflac::Tag::skip_metadata(&mut Cursor::new(data));
+    let mut buffer = Vec::with_capacity(data.len());
+    // The padding is kept for the music which embeds its own tags.
+    if options.policy == TagPolicy::Overwrite {
+        tag.remove_blocks(BlockType::Padding);
+    }
+    tag.write_to(&mut buffer).map_err(|source| Errors::Tag {
+        container: Container::Flac,
+        source: Box::new(source),
+    })?;
+    buffer.extend_from_slice(&audio);
+    Ok(buffer)
+}
+
+/// Read the fields from the vorbis comments of the flac music.
+pub(crate) fn read(data: &[u8]) -> Result<Vec<(Field, Vec<String>)>> {
+    let tag = metaflac::Tag::read_from(&mut Cursor::new(data)).map_err(|source| Errors::Tag {
+        container: Container::Flac,
+        source: Box::new(source),
+    })?;
+    let Some(mc) = tag.vorbis_comments() else {
+        return Ok(Vec::new());
+    };
+    Ok(collect_fields(|field| {
+        let key = field.vorbis_key();
+        mc.comments
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+            .flat_map(|(_, values)| values.iter().cloned())
+            .collect()
+    }))
+}
+
+/// Check if all the metadata blocks of the flac music are complete.
+fn is_metadata_complete(data: &[u8]) -> bool {
+    let mut offset = 4;
+    while let Some(header) = data.get(offset..offset + 4) {
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        offset += 4 + length;
+        if offset > data.len() {
+            return false;
+        }
+        if header[0] & 0x80 != 0 {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::tag::fixture;
+    use crate::tag::TagPolicy;
+    use crate::Ncmdump;
+
+    #[test]
+    fn test_is_metadata_complete_ok() {
+        let mut data = b"fLaC".to_vec();
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x02, 0xAA, 0xBB]);
+        data.extend_from_slice(&[0x84, 0x00, 0x00, 0x01, 0xCC, 0xFF, 0xF8]);
+        assert!(is_metadata_complete(&data));
+        assert!(is_metadata_complete(&data[..15]));
+        assert!(!is_metadata_complete(&data[..14]));
+        assert!(!is_metadata_complete(&data[..10]));
+        assert!(!is_metadata_complete(b"fLaC"));
+    }
+
+    #[test]
+    fn test_write_flac_truncated_err() -> Result<()> {
+        let file = File::open("res/test.ncm")?;
+        let mut data = Ncmdump::from_reader(file)?.get_data()?;
+        data.truncate(10000);
+        let result = write(&data, &Tags::default(), &TagOptions::default());
+        assert!(result.is_err_and(|err| err.is_truncated()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_flac_lyrics_ok() -> Result<()> {
+        let file = File::open("res/test.ncm")?;
+        let data = Ncmdump::from_reader(file)?.get_data()?;
+        let tags = Tags {
+            lyrics: vec![
+                Lyrics::parse("[00:01.00]La"),
+                Lyrics::parse("[la:chi]\n[00:01.00]啦"),
+            ],
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &TagOptions::default())?;
+        let tag = metaflac::Tag::read_from(&mut Cursor::new(output))?;
+        let lyrics = tag.get_vorbis("LYRICS").unwrap().collect::<Vec<_>>();
+        assert_eq!(lyrics, ["[00:01.00]La", "[la:chi]\n[00:01.00]啦"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_flac_tag_policy_ok() -> Result<()> {
+        let file = File::open("res/test.ncm")?;
+        let data = Ncmdump::from_reader(file)?.get_data()?;
+        let mut tag = metaflac::Tag::read_from(&mut Cursor::new(&data))?;
+        tag.set_vorbis("REPLAYGAIN_TRACK_GAIN", vec!["-1.00 dB"]);
+        tag.add_picture("image/png", PictureType::CoverFront, b"old".to_vec());
+        tag.push_block(metaflac::Block::Padding(1024));
+        let padding = tag.get_blocks(BlockType::Padding).count();
+        let mut input = Vec::new();
+        tag.write_to(&mut input)?;
+        input.extend_from_slice(&metaflac::Tag::skip_metadata(&mut Cursor::new(&data)));
+        let tags = fixture::get_tags();
+        let read = |output: &[u8]| metaflac::Tag::read_from(&mut Cursor::new(output));
+        let [output, stripped] = fixture::write_policies(&input, &tags, write)?;
+
+        let options = TagOptions {
+            policy: TagPolicy::FillMissing,
+            ..Default::default()
+        };
+        let tag = read(&write(&input, &tags, &options)?)?;
+        assert_eq!(tag.get_blocks(BlockType::Padding).count(), padding);
+        assert_eq!(
+            tag.pictures().next().map(|p| p.data.as_slice()),
+            Some(&b"old"[..])
+        );
+
+        let tag = read(&output)?;
+        assert_eq!(tag.get_blocks(BlockType::Padding).count(), 0);
+        assert_eq!(
+            tag.pictures().next().map(|p| p.data.as_slice()),
+            Some(&b"old"[..])
+        );
+        assert!(tag.get_vorbis("REPLAYGAIN_TRACK_GAIN").is_some());
+
+        let tag = read(&stripped)?;
+        assert_eq!(tag.pictures().count(), 1);
+        assert_eq!(
+            tag.pictures().next().map(|p| p.data.as_slice()),
+            Some(&tags.image[..])
+        );
+        assert!(tag.get_vorbis("REPLAYGAIN_TRACK_GAIN").is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt::Write;
+
+/// The lyrics of music, which is parsed from the lrc text.
+///
+/// The lines of the same time are kept in order, they're usually the translations of
+/// the bilingual lyrics.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Lyrics {
+    /// The language of lyrics, like `eng` or `chi`, it's read from the `[la:...]` tag
+    pub language: Option<String>,
+    /// The lines of lyrics, they're sorted by the time if the lyrics are synchronised
+    pub lines: Vec<LyricLine>,
+}
+
+/// The line of lyrics.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LyricLine {
+    /// The time of line in milliseconds, none if the lyrics are unsynchronised
+    pub time: Option<u32>,
+    /// The text of line
+    pub text: String,
+}
+
+impl Lyrics {
+    /// Parse the lrc text, the plain text is parsed as the unsynchronised lyrics.
+    ///
+    /// A line can have multiple time tags like `[00:12.34][01:23.45]`, the `[offset:...]`
+    /// tag is applied to all the times, and the word times like `<00:12.34>` are removed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use ncmdump::tag::Lyrics;
+    ///
+    /// let lyrics = Lyrics::parse("[la:eng]\n[offset:500]\n[00:01.00][00:03.00]La\n[00:02.50]Di");
+    /// assert_eq!(lyrics.language.as_deref(), Some("eng"));
+    /// assert_eq!(lyrics.to_text(), "La\nDi\nLa");
+    /// assert_eq!(lyrics.lines[0].time, Some(500));
+    /// ```
+    pub fn parse(content: &str) -> Self {
+        let content = content.trim_start_matches('\u{FEFF}');
+        let mut language = None;
+        let mut offset = 0i64;
+        let mut synced = Vec::new();
+        let mut plain = Vec::new();
+        for line in content.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+            while let Some((tag, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+                match parse_time(tag) {
+                    Some(time) => times.push(time),
+                    None => match tag.split_once(':') {
+                        Some(("la", value)) => language = Some(value.trim().to_string()),
+                        Some(("offset", value)) => {
+                            offset = value.trim().parse().unwrap_or_default();
+                        }
+                        // The other id tags like `[ar:...]` are skipped.
+                        Some((key, _)) if is_id_tag(key) => {}
+                        _ => break,
+                    },
+                }
+                rest = after;
+            }
+            let text = strip_word_times(rest.trim());
+            if !times.is_empty() {
+                synced.extend(times.into_iter().map(|time| (time, text.to_owned())));
+            } else if !rest.is_empty() && rest.len() == line.trim().len() {
+                // The line without any tag is the plain text.
+                plain.push(text);
+            }
+        }
+        // The positive offset makes the lyrics show earlier.
+        let mut lines = synced
+            .into_iter()
+            .map(|(time, text)| LyricLine {
+                time: Some(time.saturating_sub(offset).clamp(0, u32::MAX as i64) as u32),
+                text,
+            })
+            .collect::<Vec<_>>();
+        lines.sort_by_key(|line| line.time);
+        if lines.is_empty() {
+            lines = plain
+                .into_iter()
+                .map(|text| LyricLine { time: None, text })
+                .collect();
+        }
+        Self { language, lines }
+    }
+
+    /// Merge the other lyrics like the translation into the lyrics, the lines are
+    /// placed after the original lines of the same time, so they're bilingual.
+    ///
+    /// The empty lines of the other lyrics are skipped. The unsynchronised lyrics
+    /// can't be aligned, so they're only merged when the lyrics are empty.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use ncmdump::tag::Lyrics;
+    ///
+    /// let mut lyrics = Lyrics::parse("[00:01.00]Hello\n[00:02.00]World");
+    /// lyrics.merge(&Lyrics::parse("[00:01.00]你好\n[00:02.00]"));
+    /// assert_eq!(lyrics.to_text(), "Hello\n你好\nWorld");
+    /// ```
+    pub fn merge(&mut self, other: &Lyrics) {
+        if self.lines.is_empty() {
+            self.lines = other.lines.clone();
+            self.language = self.language.take().or_else(|| other.language.clone());
+            return;
+        }
+        if !self.is_synced() || !other.is_synced() {
+            return;
+        }
+        let lines = other.lines.iter().filter(|line| !line.text.is_empty());
+        self.lines.extend(lines.cloned());
+        self.lines.sort_by_key(|line| line.time);
+    }
+
+    /// Check if the lyrics are synchronised.
+    pub fn is_synced(&self) -> bool {
+        self.lines.iter().any(|line| line.time.is_some())
+    }
+
+    /// Get the unsynchronised text of lyrics, the lines are separated by `\n`.
+    pub fn to_text(&self) -> String {
+        let lines = self.lines.iter().map(|line| line.text.as_str());
+        lines.collect::<Vec<_>>().join("\n")
+    }
+
+    /// Format the lyrics into the lrc text, the offset is already applied to the times.
+    pub fn to_lrc(&self) -> String {
+        let mut output = String::new();
+        if let Some(language) = &self.language {
+            let _ = writeln!(output, "[la:{}]", language);
+        }
+        for line in &self.lines {
+            if let Some(time) = line.time {
+                let centis = time / 10;
+                let _ = write!(
+                    output,
+                    "[{:02}:{:02}.{:02}]",
+                    centis / 6000,
+                    centis / 100 % 60,
+                    centis % 100
+                );
+            }
+            let _ = writeln!(output, "{}", line.text);
+        }
+        output.truncate(output.trim_end().len());
+        output
+    }
+
+    /// Get the language code and description of the id3 lyrics frames, the language
+    /// which isn't a three letter code is written into the description.
+    pub(super) fn id3_language(&self) -> (String, String) {
+        match self.language.as_deref() {
+            Some(language)
+                if language.len() == 3 && language.bytes().all(|c| c.is_ascii_alphabetic()) =>
+            {
+                (language.to_ascii_lowercase(), String::new())
+            }
+            Some(language) => ("XXX".to_string(), language.to_string()),
+            None => ("XXX".to_string(), String::new()),
+        }
+    }
+}
+
+/// Parse the time tag like `mm:ss`, `mm:ss.xx`, `mm:ss.xxx` or `mm:ss:xx` into
+/// milliseconds, it's none if the time overflows.
+fn parse_time(tag: &str) -> Option<i64> {
+    let (minutes, rest) = tag.split_once(':')?;
+    let (seconds, fraction) = match rest.split_once(['.', ':']) {
+        Some((seconds, fraction)) => (seconds, fraction),
+        None => (rest, ""),
+    };
+    let digits = |value: &str| !value.is_empty() && value.bytes().all(|c| c.is_ascii_digit());
+    if !digits(minutes) || !digits(seconds) || !(fraction.is_empty() || digits(fraction)) {
+        return None;
+    }
+    let millis = match fraction.len() {
+        0 => 0,
+        1 => fraction.parse::<i64>().ok()? * 100,
+        2 => fraction.parse::<i64>().ok()? * 10,
+        _ => fraction[..3].parse::<i64>().ok()?,
+    };
+    minutes
+        .parse::<i64>()
+        .ok()?
+        .checked_mul(60_000)?
+        .checked_add(seconds.parse::<i64>().ok()?.checked_mul(1000)?)?
+        .checked_add(millis)
+}
+
+/// Check if the key is the id tag of lrc, like `ar`, `ti` or `al`.
+fn is_id_tag(key: &str) -> bool {
+    !key.is_empty() && key.len() <= 8 && key.bytes().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Remove the word times of the enhanced lrc, like `<00:12.34>`.
+fn strip_word_times(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        match rest[start..].find('>') {
+            Some(end) if parse_time(&rest[start + 1..start + end]).is_some() => {
+                output.push_str(&rest[..start]);
+                rest = &rest[start + end + 1..];
+            }
+            _ => {
+                output.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_ok() {
+        assert_eq!(parse_time("01:02"), Some(62_000));
+        assert_eq!(parse_time("01:02.3"), Some(62_300));
+        assert_eq!(parse_time("01:02.34"), Some(62_340));
+        assert_eq!(parse_time("01:02.345"), Some(62_345));
+        assert_eq!(parse_time("01:02:34"), Some(62_340));
+        assert_eq!(parse_time("ar:Artist"), None);
+        assert_eq!(parse_time("01:"), None);
+        assert_eq!(parse_time("99999999999999999:00"), None);
+    }
+
+    #[test]
+    fn test_parse_lyrics_ok() {
+        let content = "\u{FEFF}[ti:Title]\n[ar:Artist]\n[offset:-1000]\n\n\
+                       [00:02.00]<00:02.00>Second <00:02.50>line\n\
+                       [00:01.00][00:03.00]Chorus\n[00:01.00]副歌\n[00:04.00]";
+        let lyrics = Lyrics::parse(content);
+        assert_eq!(lyrics.language, None);
+        let lines = lyrics
+            .lines
+            .iter()
+            .map(|line| (line.time, line.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                (Some(2000), "Chorus"),
+                (Some(2000), "副歌"),
+                (Some(3000), "Second line"),
+                (Some(4000), "Chorus"),
+                (Some(5000), ""),
+            ]
+        );
+        assert!(lyrics.is_synced());
+        assert_eq!(
+            lyrics.to_lrc(),
+            "[00:02.00]Chorus\n[00:02.00]副歌\n[00:03.00]Second line\n[00:04.00]Chorus\n[00:05.00]"
+        );
+    }
+
+    #[test]
+    fn test_parse_plain_lyrics_ok() {
+        let lyrics = Lyrics::parse("[la:Chinese]\nFirst line\n\nSecond [line]\n");
+        assert!(!lyrics.is_synced());
+        assert_eq!(lyrics.to_text(), "First line\nSecond [line]");
+        assert_eq!(
+            lyrics.id3_language(),
+            ("XXX".to_string(), "Chinese".to_string())
+        );
+        assert_eq!(lyrics.to_lrc(), "[la:Chinese]\nFirst line\nSecond [line]");
+    }
+
+    #[test]
+    fn test_parse_overflow_lyrics_ok() {
+        let lyrics = Lyrics::parse(
+            "[offset:-9223372036854775808]\n[00:01.00]First\n[99999999999999999:00]Second",
+        );
+        let lines = lyrics
+            .lines
+            .iter()
+            .map(|line| (line.time, line.text.as_str()))
+            .collect::<Vec<_>>();
+        // The line with the overflowed time isn't synced, and the offset is saturated.
+        assert_eq!(lines, [(Some(u32::MAX), "First")]);
+    }
+
+    #[test]
+    fn test_merge_lyrics_ok() {
+        let mut lyrics = Lyrics::parse("[00:01.00]一\n[00:03.00]三\n[00:03.00]参");
+        lyrics.merge(&Lyrics::parse(
+            "[by:Someone]\n[00:03.00]Three\n[00:02.00]Two",
+        ));
+        lyrics.merge(&Lyrics::parse("[00:01.00]ichi\n[00:02.00]"));
+        assert_eq!(
+            lyrics.to_lrc(),
+            "[00:01.00]一\n[00:01.00]ichi\n[00:02.00]Two\n[00:03.00]三\n[00:03.00]参\n[00:03.00]Three"
+        );
+        lyrics.merge(&Lyrics::parse("Plain"));
+        assert_eq!(lyrics.lines.len(), 6);
+
+        let mut lyrics = Lyrics::default();
+        lyrics.merge(&Lyrics::parse("[la:eng]\nPlain"));
+        assert_eq!(lyrics.language.as_deref(), Some("eng"));
+        assert_eq!(lyrics.to_text(), "Plain");
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use super::template::{self, Segment};
+use super::Field;
+use crate::error::{Errors, Result};
+use crate::NcmInfo;
+
+/// The variables of templates, and whether the variable is a list.
+const VARIABLES: [(&str, bool); 11] = [
+    ("name", false),
+    ("id", false),
+    ("album", false),
+    ("album_id", false),
+    ("artist", true),
+    ("bitrate", false),
+    ("duration", false),
+    ("format", false),
+    ("mv_id", false),
+    ("alias", true),
+    ("trans_names", true),
+];
+
+/// The mapping of tags, it's usually loaded from a toml file.
+///
+/// The templates of `fields` are applied to all formats, the templates of `id3` and
+/// `vorbis` are applied to the frames or comments of the format, and override the
+/// fields. The field is kept as default if none of its templates can be rendered, and
+/// it's removed if the template is empty.
+///
+/// ```toml
+/// [fields]
+/// title = ["{name} ({alias[0]})", "{name}"]
+///
+/// [id3]
+/// TIT3 = ""
+/// "TXXX:TRANSLATION" = "{trans_names[0]}"
+///
+/// [vorbis]
+/// TRANSLATION = "{trans_names}"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagMap {
+    /// The templates of the fields
+    pub fields: BTreeMap<Field, Template>,
+    /// The templates of the id3 frames, the key is the id of text or link frame, or
+    /// `TXXX:description` for the extended text
+    pub id3: BTreeMap<String, Template>,
+    /// The templates of the vorbis comments, the key is the name of comment
+    pub vorbis: BTreeMap<String, Template>,
+}
+
+impl TagMap {
+    /// Check the keys and the syntax of the templates.
+    pub fn validate(&self) -> Result<()> {
+        let templates = self
+            .fields
+            .iter()
+            .map(|(field, t)| (format!("fields.{}", field.name()), t))
+            .chain(self.id3.iter().map(|(key, t)| (format!("id3.{}", key), t)))
+            .chain(
+                self.vorbis
+                    .iter()
+                    .map(|(key, t)| (format!("vorbis.{}", key), t)),
+            );
+        for (key, template) in templates {
+            for source in template.sources() {
+                parse(source).map_err(|reason| Errors::InvalidTagMap {
+                    key: key.to_owned(),
+                    reason,
+                })?;
+            }
+        }
+        if let Some(key) = self.id3.keys().find(|key| !is_id3_key(key)) {
+            return Err(Errors::InvalidTagMap {
+                key: format!("id3.{}", key),
+                reason: "only the text, link and TXXX frames are supported".to_string(),
+            });
+        }
+        if let Some(key) = self.vorbis.keys().find(|key| !is_vorbis_key(key)) {
+            return Err(Errors::InvalidTagMap {
+                key: format!("vorbis.{}", key),
+                reason: "invalid name of vorbis comment".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Apply the field templates to the values of fields.
+    pub(super) fn apply_fields(
+        &self,
+        info: &NcmInfo,
+        separator: &str,
+        fields: &mut Vec<(Field, Vec<String>)>,
+    ) {
+        for (field, template) in &self.fields {
+            let Some(value) = template.render_with(info, separator) else {
+                continue;
+            };
+            fields.retain(|(f, _)| f != field);
+            if !value.is_empty() {
+                fields.push((*field, vec![value]));
+            }
+        }
+    }
+
+    /// Apply the id3 templates to the frames, the key of frame is told apart by the id
+    /// and the description of extended text.
+    pub(super) fn apply_id3(
+        &self,
+        info: &NcmInfo,
+        separator: &str,
+        frames: &mut Vec<(String, Vec<String>)>,
+    ) {
+        apply(&self.id3, info, separator, frames, |a, b| a == b);
+    }
+
+    /// Apply the vorbis templates to the comments, the name of comment is case
+    /// insensitive.
+    pub(super) fn apply_vorbis(
+        &self,
+        info: &NcmInfo,
+        separator: &str,
+        comments: &mut Vec<(String, Vec<String>)>,
+    ) {
+        apply(&self.vorbis, info, separator, comments, |a, b| {
+            a.eq_ignore_ascii_case(b)
+        });
+    }
+}
+
+/// Check if the key is the id of text or link frame, or `TXXX:description`.
+pub(super) fn is_id3_key(key: &str) -> bool {
+    match key.strip_prefix("TXXX:") {
+        Some(description) => !description.is_empty(),
+        None => {
+            key.len() == 4
+                && key.starts_with(['T', 'W'])
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+                && key != "TXXX"
+                && key != "WXXX"
+        }
+    }
+}
+
+/// Check if the key is the name of vorbis comment, it's printable ascii except `=`.
+fn is_vorbis_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| (' '..='}').contains(&c) && c != '=')
+}
+
+/// Apply the templates to the tags, the tag is replaced if its key is matched.
+fn apply<F>(
+    templates: &BTreeMap<String, Template>,
+    info: &NcmInfo,
+    separator: &str,
+    tags: &mut Vec<(String, Vec<String>)>,
+    matches: F,
+) where
+    F: Fn(&str, &str) -> bool,
+{
+    for (key, template) in templates {
+        let Some(value) = template.render_with(info, separator) else {
+            continue;
+        };
+        tags.retain(|(k, _)| !matches(k, key));
+        if !value.is_empty() {
+            tags.push((key.to_owned(), vec![value]));
+        }
+    }
+}
+
+/// The template of tag, such as `{name} ({alias[0]})`.
+///
+/// It's a string or a list of strings, the first one whose variables are all present
+/// is used. The variables are the fields of [`NcmInfo`], the `artist`, `alias` and
+/// `trans_names` are lists which can be indexed, they're joined with the separator of
+/// [`TagOptions`](super::TagOptions) if not. The variables can fall back to the others
+/// and be truncated, see [`template`](super::template) for the syntax.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Template {
+    /// The single template
+    Single(String),
+    /// The templates which are tried in order
+    Fallback(Vec<String>),
+}
+
+impl Template {
+    fn sources(&self) -> &[String] {
+        match self {
+            Self::Single(source) => std::slice::from_ref(source),
+            Self::Fallback(sources) => sources,
+        }
+    }
+
+    /// Render the template with the information, none if all the templates have
+    /// missing variables. The lists are joined with `/`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::fs::File;
+    /// #
+    /// # use anyhow::Result;
+    /// # use ncmdump::tag::Template;
+    /// # use ncmdump::Ncmdump;
+    /// #
+    /// # fn main() -> Result<()> {
+    /// let info = Ncmdump::from_reader(File::open("res/test.ncm")?)?.get_info()?;
+    /// let template = Template::Single("{artist[0]} - {name} ({alias[0]})".into());
+    /// assert_eq!(
+    ///     template.render(&info),
+    ///     Some("华晨宇 - 寒鸦少年 (电视剧《斗破苍穹》主题曲)".into()),
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn render(&self, info: &NcmInfo) -> Option<String> {
+        self.render_with(info, "/")
+    }
+
+    /// Render the template, the lists are joined with the separator.
+    pub(super) fn render_with(&self, info: &NcmInfo, separator: &str) -> Option<String> {
+        self.sources().iter().find_map(|source| {
+            let mut output = String::new();
+            for segment in parse(source).ok()? {
+                match segment {
+                    Segment::Text(text) => output.push_str(&text),
+                    Segment::Variable(variable) => {
+                        let value = variable.render(|name, index| {
+                            let values = get_values(info, name);
+                            let value = match index {
+                                Some(index) => values.get(index).cloned(),
+                                None => Some(values.join(separator)),
+                            };
+                            value.filter(|value| !value.is_empty())
+                        })?;
+                        output.push_str(&value);
+                    }
+                }
+            }
+            Some(output)
+        })
+    }
+}
+
+/// Parse the template into segments, the variables are checked.
+fn parse(source: &str) -> std::result::Result<Vec<Segment>, String> {
+    let segments = template::parse(source)?;
+    for segment in &segments {
+        let Segment::Variable(variable) = segment else {
+            continue;
+        };
+        for (name, index) in &variable.alternatives {
+            match VARIABLES.iter().find(|(n, _)| n == name) {
+                None => return Err(format!("unknown variable `{}`", name)),
+                Some((_, false)) if index.is_some() => {
+                    return Err(format!("`{}` is not a list", name))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(segments)
+}
+
+/// Get the values of the variable, the missing value is empty.
+fn get_values(info: &NcmInfo, name: &str) -> Vec<String> {
+    let number = |value: Option<u64>| value.map(|value| value.to_string());
+    match name {
+        "name" => vec![info.name.to_owned()],
+        "id" => number(info.id).into_iter().collect(),
+        "album" => vec![info.album.to_owned()],
+        "album_id" => number(info.album_id).into_iter().collect(),
+        "artist" => info
+            .artist
+            .iter()
+            .map(|(name, _)| name.to_owned())
+            .collect(),
+        "bitrate" => number(info.bitrate).into_iter().collect(),
+        "duration" => number(info.duration).into_iter().collect(),
+        "format" => vec![info.format.to_owned()],
+        "mv_id" => number(info.mv_id).into_iter().collect(),
+        "alias" => info.alias.clone().unwrap_or_default(),
+        "trans_names" => info.trans_names.clone().unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::tag::fixture::get_info;
+
+    #[test]
+    fn test_parse_err() {
+        assert!(parse("{name").is_err());
+        assert!(parse("{unknown}").is_err());
+        assert!(parse("{name[0]}").is_err());
+        assert!(parse("{alias[0]|name[0]}").is_err());
+    }
+
+    #[test]
+    fn test_render_ok() {
+        let info = get_info();
+        let render = |template: Template| template.render(&info);
+        assert_eq!(
+            render(Template::Single("{name} ({alias[0]})".into())),
+            Some("Golden Hour (Alias)".into()),
+        );
+        assert_eq!(
+            render(Template::Single("{artist} {id}".into())),
+            Some("JVKE/Other 1".into()),
+        );
+        assert_eq!(render(Template::Single("{trans_names[0]}".into())), None);
+        assert_eq!(render(Template::Single("{duration}".into())), None);
+        assert_eq!(
+            render(Template::Fallback(vec![
+                "{name} ({trans_names[0]})".into(),
+                "{name}".into(),
+            ])),
+            Some("Golden Hour".into()),
+        );
+        assert_eq!(render(Template::Single("".into())), Some("".into()));
+        assert_eq!(
+            render(Template::Single("{trans_names[0]|alias[0]:3}".into())),
+            Some("Ali".into()),
+        );
+        assert_eq!(
+            Template::Single("{artist}".into()).render_with(&info, "; "),
+            Some("JVKE; Other".into()),
+        );
+    }
+
+    #[test]
+    fn test_tag_map_apply_ok() -> Result<()> {
+        let map = serde_json::from_str::<TagMap>(
+            r#"{
+                "fields": {"title": "{name} ({alias[0]})", "subtitle": "", "album": "{mv_id}"},
+                "id3": {"TXXX:NETEASE_ID": "{id}"},
+                "vorbis": {"title": "{name}"}
+            }"#,
+        )?;
+        map.validate()?;
+        let info = get_info();
+        let mut fields = vec![
+            (Field::Title, vec!["Golden Hour".into()]),
+            (Field::Album, vec!["Golden Hour".into()]),
+            (Field::Subtitle, vec!["Alias".into()]),
+        ];
+        map.apply_fields(&info, "/", &mut fields);
+        assert_eq!(
+            fields,
+            [
+                (Field::Album, vec!["Golden Hour".into()]),
+                (Field::Title, vec!["Golden Hour (Alias)".into()]),
+            ],
+        );
+
+        let mut frames = vec![("TIT2".to_string(), vec!["Golden Hour".to_string()])];
+        map.apply_id3(&info, "/", &mut frames);
+        assert_eq!(frames[1], ("TXXX:NETEASE_ID".into(), vec!["1".into()]));
+
+        let mut comments = vec![("TITLE".to_string(), vec!["Title".to_string()])];
+        map.apply_vorbis(&info, "/", &mut comments);
+        assert_eq!(comments, [("title".into(), vec!["Golden Hour".into()])]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tag_map_validate_err() -> Result<()> {
+        let invalid = [
+            r#"{"fields": {"title": "{title}"}}"#,
+            r#"{"id3": {"TXXX:": "{name}"}}"#,
+            r#"{"id3": {"APIC": "{name}"}}"#,
+            r#"{"id3": {"TXXX": "{name}"}}"#,
+            r#"{"vorbis": {"TITLE=": "{name}"}}"#,
+        ];
+        for json in invalid {
+            let map = serde_json::from_str::<TagMap>(json)?;
+            let result = map.validate();
+            assert!(
+                matches!(result, Err(Errors::InvalidTagMap { .. })),
+                "{}",
+                json
+            );
+        }
+        assert!(serde_json::from_str::<TagMap>(r#"{"fields": {"unknown": ""}}"#).is_err());
+        assert!(serde_json::from_str::<TagMap>(r#"{"ape": {}}"#).is_err());
+        Ok(())
+    }
+}
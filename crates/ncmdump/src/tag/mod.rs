@@ -0,0 +1,762 @@
+//! Write the tags into the decrypted music, the flac, mp3, ogg vorbis, opus and mp4
+//! music are supported.
+//!
+//! The title, album, artist, album artist, subtitle, song url, netease ids, cover and
+//! comment are mapped from the [`NcmInfo`] of the encrypted file, the tags which are
+//! already embedded in the music are kept.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::fs::File;
+//!
+//! use anyhow::Result;
+//! use ncmdump::tag::{self, TagOptions};
+//! use ncmdump::Ncmdump;
+//!
+//! fn main() -> Result<()> {
+//!     let file = File::open("res/test.ncm")?;
+//!     let mut ncm = Ncmdump::from_reader(file)?;
+//!     let mut target = File::create("res/test.flac")?;
+//!     tag::write_tagged(&mut ncm, &mut target, &TagOptions::default())?;
+//!     Ok(())
+//! }
+//! ```
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek, Write};
+
+use serde::Deserialize;
+
+use crate::error::{Errors, Result};
+use crate::progress::{self, CancelToken};
+#[cfg(feature = "qmcdump")]
+use crate::QmcDump;
+use crate::{NcmInfo, Ncmdump};
+
+pub use self::cover::{to_jpeg, CoverOptions};
+pub use self::lyrics::{LyricLine, Lyrics};
+pub use self::map::{TagMap, Template};
+
+mod cover;
+mod flac;
+mod lyrics;
+mod map;
+mod mp3;
+mod mp4;
+mod ogg;
+pub mod template;
+
+/// The container format of the decrypted music.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Container {
+    /// The flac music
+    Flac,
+    /// The mp3 music with id3 tag
+    Mp3,
+    /// The ogg vorbis music
+    Ogg,
+    /// The ogg opus music
+    Opus,
+    /// The mp4 music, usually it's aac
+    Mp4,
+}
+
+impl Container {
+    /// Sniff the container format from the beginning bytes of the music.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use ncmdump::tag::Container;
+    /// #
+    /// assert_eq!(Container::sniff(b"fLaC"), Some(Container::Flac));
+    /// assert_eq!(Container::sniff(b"RIFF"), None);
+    /// ```
+    pub fn sniff(head: &[u8]) -> Option<Self> {
+        if head.get(4..8) == Some(b"ftyp") {
+            return Some(Self::Mp4);
+        }
+        match head.get(..4)? {
+            [0x66, 0x4C, 0x61, 0x43] => Some(Self::Flac),
+            [0x49, 0x44, 0x33, _] => Some(Self::Mp3),
+            // The frame sync of the mpeg audio without tag, the layer bits of the aac
+            // and flac frames are zero.
+            [0xFF, b, _, _] if b & 0xE0 == 0xE0 && b & 0x06 != 0 => Some(Self::Mp3),
+            [0x4F, 0x67, 0x67, 0x53] => ogg::Codec::sniff(head).map(|codec| codec.container()),
+            _ => None,
+        }
+    }
+
+    /// The file extension of the container.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Flac => "flac",
+            Self::Mp3 => "mp3",
+            Self::Ogg => "ogg",
+            Self::Opus => "opus",
+            Self::Mp4 => "m4a",
+        }
+    }
+}
+
+impl Display for Container {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// The tags of the encrypted file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Tags {
+    /// The information of music, none if the file doesn't contain it
+    pub info: Option<NcmInfo>,
+    /// The cover image, empty if the file doesn't contain it
+    pub image: Vec<u8>,
+    /// The `163 key(Don't modify):...` comment
+    pub comment: Option<String>,
+    /// The lyrics in different languages, empty if there are no lyrics
+    pub lyrics: Vec<Lyrics>,
+}
+
+/// The options of writing tags.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TagOptions {
+    /// Write the `163 key` string into the comment tag, default is true.
+    /// The netease client use it to recognise the converted music.
+    pub comment: bool,
+    /// The policy of the tags which are already embedded in the music, default is
+    /// overwriting them. The same policy is applied to all the containers.
+    pub policy: TagPolicy,
+    /// Keep the embedded covers, ReplayGain and lyrics when the tags are overwritten,
+    /// default is true. The cover is replaced and the others are removed if not.
+    pub keep_extras: bool,
+    /// The options of processing the cover image
+    pub cover: CoverOptions,
+    /// Write the synchronised lyrics frame `SYLT` into the mp3 music besides the
+    /// `USLT` frame, default is false
+    pub synced_lyrics: bool,
+    /// The mapping of tags, the default tags are written if it's empty
+    pub map: TagMap,
+    /// The separator to join the multiple values, default is `/`.
+    /// It's used by the formats which can't store multiple values, like mp4 and id3v2.3.
+    pub separator: String,
+    /// The version of id3v2 tag in the mp3 music, default is v2.4
+    pub id3_version: Id3Version,
+    /// Append the id3v1 tag to the mp3 music for the legacy devices, default is false
+    pub id3v1: bool,
+}
+
+impl Default for TagOptions {
+    fn default() -> Self {
+        Self {
+            comment: true,
+            policy: TagPolicy::default(),
+            keep_extras: true,
+            cover: CoverOptions::default(),
+            synced_lyrics: false,
+            map: TagMap::default(),
+            separator: "/".to_string(),
+            id3_version: Id3Version::default(),
+            id3v1: false,
+        }
+    }
+}
+
+impl TagOptions {
+    /// Check if the music is left untouched, it's kept if it's already tagged.
+    fn keeps(&self, tagged: bool) -> bool {
+        self.policy == TagPolicy::Keep && tagged
+    }
+
+    /// Check if the value should be written, the embedded tags and empty values are
+    /// skipped when only the missing tags are filled.
+    fn writes(&self, embedded: bool, value: &[u8]) -> bool {
+        self.policy != TagPolicy::FillMissing || !(embedded || value.is_empty())
+    }
+
+    /// Check if the cover or lyrics should be written, the embedded ones are only
+    /// replaced if the extras aren't kept.
+    fn writes_extra(&self, embedded: bool) -> bool {
+        !embedded || self.removes_extras()
+    }
+
+    /// Check if the embedded ReplayGain and lyrics should be removed.
+    fn removes_extras(&self) -> bool {
+        self.policy == TagPolicy::Overwrite && !self.keep_extras
+    }
+}
+
+/// The policy of the tags which are already embedded in the music.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TagPolicy {
+    /// Keep the music untouched if it's already tagged, the tags are written if not
+    Keep,
+    /// Overwrite the embedded tags with the information
+    #[default]
+    Overwrite,
+    /// Only write the tags which are missing in the music.
+    /// It's used for the music which already embeds its own tags, like the qmc music.
+    FillMissing,
+}
+
+/// The version of id3v2 tag.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Id3Version {
+    /// The id3v2.3, the v2.4 only frames are converted or removed
+    V23,
+    /// The id3v2.4, the multiple values are separated by null
+    #[default]
+    V24,
+}
+
+/// The dumper which can provide the tags of the encrypted file.
+pub trait Dump: Read {
+    /// Get the tags of the encrypted file.
+    fn get_tags(&mut self) -> Result<Tags>;
+}
+
+impl<S> Dump for Ncmdump<S>
+where
+    S: Read + Seek,
+{
+    fn get_tags(&mut self) -> Result<Tags> {
+        Ok(Tags {
+            info: Some(self.get_info()?),
+            image: self.get_image()?,
+            comment: Some(self.get_comment()?),
+            lyrics: Vec::new(),
+        })
+    }
+}
+
+#[cfg(feature = "qmcdump")]
+impl<S> Dump for QmcDump<S>
+where
+    S: Read,
+{
+    fn get_tags(&mut self) -> Result<Tags> {
+        Ok(Tags::default())
+    }
+}
+
+/// Write the tags into the decrypted music, and return the tagged music.
+///
+/// The container format is sniffed from the music, [`Errors::InvalidFileType`] is
+/// returned if it's not supported.
+pub fn apply(data: &[u8], tags: &Tags, options: &TagOptions) -> Result<Vec<u8>> {
+    let container = Container::sniff(data).ok_or(Errors::InvalidFileType)?;
+    let image = match options.cover.embed {
+        true => options.cover.process(&tags.image)?,
+        false => Vec::new(),
+    };
+    let tags = &Tags {
+        image,
+        ..tags.clone()
+    };
+    match container {
+        Container::Flac => flac::write(data, tags, options),
+        Container::Mp3 => mp3::write(data, tags, options),
+        Container::Ogg | Container::Opus => ogg::write(data, tags, options),
+        Container::Mp4 => mp4::write(data, tags, options),
+    }
+}
+
+/// Read the fields which are embedded in the decrypted music, the missing fields are
+/// skipped.
+///
+/// The container format is sniffed from the music, [`Errors::InvalidFileType`] is
+/// returned if it's not supported.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::fs::File;
+/// #
+/// # use anyhow::Result;
+/// # use ncmdump::tag::{self, Field, TagOptions};
+/// # use ncmdump::Ncmdump;
+/// #
+/// # fn main() -> Result<()> {
+/// let mut ncm = Ncmdump::from_reader(File::open("res/test.ncm")?)?;
+/// let mut music = Vec::new();
+/// tag::write_tagged(&mut ncm, &mut music, &TagOptions::default())?;
+/// let fields = tag::read_fields(&music)?;
+/// assert!(fields.contains(&(Field::Title, vec!["寒鸦少年".to_string()])));
+/// # Ok(())
+/// # }
+/// ```
+pub fn read_fields(data: &[u8]) -> Result<Vec<(Field, Vec<String>)>> {
+    match Container::sniff(data).ok_or(Errors::InvalidFileType)? {
+        Container::Flac => flac::read(data),
+        Container::Mp3 => mp3::read(data),
+        Container::Ogg | Container::Opus => ogg::read(data),
+        Container::Mp4 => mp4::read(data),
+    }
+}
+
+/// Decrypt the music from the dumper, write the tagged music into the writer, and
+/// return the size of bytes written.
+///
+/// The music is read from the current position of the dumper before the tags.
+pub fn write_tagged<D, W>(dump: &mut D, writer: &mut W, options: &TagOptions) -> Result<u64>
+where
+    D: Dump + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut data = Vec::new();
+    progress::copy(dump, &mut data, 0, 0, &|_, _| {}, &CancelToken::new())?;
+    let tags = dump.get_tags()?;
+    let buffer = apply(&data, &tags, options)?;
+    writer.write_all(&buffer)?;
+    Ok(buffer.len() as u64)
+}
+
+/// The error of the malformed music, which can't be tagged.
+#[derive(Debug)]
+struct MalformedError(&'static str);
+
+impl Display for MalformedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for MalformedError {}
+
+/// The url of netease song page, the id of song is appended.
+const SONG_URL: &str = "https://music.163.com/song?id=";
+
+/// The fields of tags which are mapped from the [`NcmInfo`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    /// The name of music
+    Title,
+    /// The album of music
+    Album,
+    /// The artists of music
+    Artist,
+    /// The first artist of music
+    AlbumArtist,
+    /// The alias of music
+    Subtitle,
+    /// The url of netease song page
+    Website,
+    /// The id of music
+    NeteaseId,
+    /// The id of album
+    NeteaseAlbumId,
+}
+
+/// All the fields, in the order of [`Field`].
+const FIELDS: [Field; 8] = [
+    Field::Title,
+    Field::Album,
+    Field::Artist,
+    Field::AlbumArtist,
+    Field::Subtitle,
+    Field::Website,
+    Field::NeteaseId,
+    Field::NeteaseAlbumId,
+];
+
+impl Field {
+    /// Get the field by the name in the [`TagMap`], like `album_artist`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        FIELDS.into_iter().find(|field| field.name() == name)
+    }
+
+    /// The name of field in the [`TagMap`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Album => "album",
+            Self::Artist => "artist",
+            Self::AlbumArtist => "album_artist",
+            Self::Subtitle => "subtitle",
+            Self::Website => "website",
+            Self::NeteaseId => "netease_id",
+            Self::NeteaseAlbumId => "netease_album_id",
+        }
+    }
+
+    /// The id of id3 frame, or `TXXX:description` for the extended text.
+    fn id3_key(&self) -> &'static str {
+        match self {
+            Self::Title => "TIT2",
+            Self::Album => "TALB",
+            Self::Artist => "TPE1",
+            Self::AlbumArtist => "TPE2",
+            Self::Subtitle => "TIT3",
+            Self::Website => "WOAF",
+            Self::NeteaseId => "TXXX:NETEASE_ID",
+            Self::NeteaseAlbumId => "TXXX:NETEASE_ALBUMID",
+        }
+    }
+
+    /// The name of vorbis comment.
+    fn vorbis_key(&self) -> &'static str {
+        match self {
+            Self::Title => "TITLE",
+            Self::Album => "ALBUM",
+            Self::Artist => "ARTIST",
+            Self::AlbumArtist => "ALBUMARTIST",
+            Self::Subtitle => "SUBTITLE",
+            Self::Website => "WEBSITE",
+            Self::NeteaseId => "NETEASE_ID",
+            Self::NeteaseAlbumId => "NETEASE_ALBUMID",
+        }
+    }
+}
+
+/// Map the information into the fields, the optional fields are skipped if they're
+/// missing. The templates of the tag map in the options are applied.
+pub fn get_fields(info: &NcmInfo, options: &TagOptions) -> Vec<(Field, Vec<String>)> {
+    let artists = get_artists(info);
+    let alias = info
+        .alias
+        .iter()
+        .flatten()
+        .filter(|alias| !alias.is_empty())
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut fields = vec![
+        (Field::Title, vec![info.name.to_owned()]),
+        (Field::Album, vec![info.album.to_owned()]),
+    ];
+    let album_artist = artists.first().cloned();
+    fields.push((Field::Artist, artists));
+    if let Some(artist) = album_artist {
+        fields.push((Field::AlbumArtist, vec![artist]));
+    }
+    if !alias.is_empty() {
+        fields.push((Field::Subtitle, alias));
+    }
+    if let Some(id) = info.id {
+        fields.push((Field::Website, vec![format!("{}{}", SONG_URL, id)]));
+        fields.push((Field::NeteaseId, vec![id.to_string()]));
+    }
+    if let Some(id) = info.album_id {
+        fields.push((Field::NeteaseAlbumId, vec![id.to_string()]));
+    }
+    options
+        .map
+        .apply_fields(info, &options.separator, &mut fields);
+    fields
+}
+
+/// Collect the embedded values of all the fields, the empty values are skipped.
+fn collect_fields<F>(get: F) -> Vec<(Field, Vec<String>)>
+where
+    F: Fn(Field) -> Vec<String>,
+{
+    FIELDS
+        .into_iter()
+        .filter_map(|field| {
+            let values = get(field)
+                .into_iter()
+                .filter(|value| !value.is_empty())
+                .collect::<Vec<_>>();
+            (!values.is_empty()).then_some((field, values))
+        })
+        .collect()
+}
+
+/// Check if the vorbis comment is the ReplayGain or lyrics, the name is case insensitive.
+fn is_extra_comment(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    name.starts_with("REPLAYGAIN_") || name == "LYRICS" || name == "UNSYNCEDLYRICS"
+}
+
+/// Map the information into the vorbis comments.
+fn get_vorbis_comments(info: &NcmInfo, options: &TagOptions) -> Vec<(String, Vec<String>)> {
+    let mut comments = get_fields(info, options)
+        .into_iter()
+        .map(|(field, values)| (field.vorbis_key().to_string(), values))
+        .collect();
+    options
+        .map
+        .apply_vorbis(info, &options.separator, &mut comments);
+    comments
+}
+
+/// Map the information into the id3 frames, the key is the id of frame or
+/// `TXXX:description`.
+fn get_id3_frames(info: &NcmInfo, options: &TagOptions) -> Vec<(String, Vec<String>)> {
+    let mut frames = get_fields(info, options)
+        .into_iter()
+        .map(|(field, values)| (field.id3_key().to_string(), values))
+        .collect();
+    options.map.apply_id3(info, &options.separator, &mut frames);
+    frames
+}
+
+/// Get the mime type of the image by the magic bytes.
+fn get_image_mime_type(bytes: &[u8]) -> &'static str {
+    match bytes {
+        // All the jpeg markers are accepted, not only the JFIF and EXIF ones.
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        _ => image::guess_format(bytes)
+            .map(|format| format.to_mime_type())
+            .unwrap_or("image/*"),
+    }
+}
+
+/// Get the artist names of the information.
+fn get_artists(info: &NcmInfo) -> Vec<String> {
+    info.artist.iter().map(|item| item.0.to_owned()).collect()
+}
+
+/// The fixtures which are shared by the tests of containers.
+#[cfg(test)]
+pub(crate) mod fixture {
+    use anyhow::Result;
+
+    use super::*;
+
+    /// The beginning of a jpeg image.
+    pub(crate) const IMAGE: [u8; 12] = [0xFF, 0xD8, 0xFF, 0xE0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    /// Get the information of the test music.
+    pub(crate) fn get_info() -> NcmInfo {
+        NcmInfo {
+            name: "Golden Hour".into(),
+            id: Some(1),
+            album: "Golden Hour Album".into(),
+            album_id: Some(3),
+            artist: vec![("JVKE".into(), Some(2)), ("Other".into(), None)],
+            bitrate: None,
+            duration: None,
+            format: "flac".into(),
+            mv_id: None,
+            alias: Some(vec!["Alias".into()]),
+            trans_names: None,
+        }
+    }
+
+    /// Get the tags of the test music with the information, cover and comment.
+    pub(crate) fn get_tags() -> Tags {
+        Tags {
+            info: Some(get_info()),
+            image: IMAGE.to_vec(),
+            comment: Some("163 key(Don't modify):abc".into()),
+            lyrics: Vec::new(),
+        }
+    }
+
+    /// Write the tags by the policies, the music is unchanged with the `Keep` policy.
+    /// The outputs of the default options and the options without extras are returned.
+    pub(crate) fn write_policies<F>(data: &[u8], tags: &Tags, write: F) -> Result<[Vec<u8>; 2]>
+    where
+        F: Fn(&[u8], &Tags, &TagOptions) -> crate::error::Result<Vec<u8>>,
+    {
+        let options = TagOptions {
+            policy: TagPolicy::Keep,
+            ..Default::default()
+        };
+        assert_eq!(write(data, tags, &options)?, data);
+        let output = write(data, tags, &TagOptions::default())?;
+        let options = TagOptions {
+            keep_extras: false,
+            ..Default::default()
+        };
+        let stripped = write(data, tags, &options)?;
+        Ok([output, stripped])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn test_container_sniff_ok() {
+        assert_eq!(Container::sniff(b"fLaC\x00"), Some(Container::Flac));
+        assert_eq!(Container::sniff(b"ID3\x04"), Some(Container::Mp3));
+        assert_eq!(Container::sniff(b"ID3"), None);
+        assert_eq!(
+            Container::sniff(&[0xFF, 0xFB, 0x90, 0x00]),
+            Some(Container::Mp3)
+        );
+        assert_eq!(Container::sniff(&[0xFF, 0xF1, 0x50, 0x80]), None);
+        assert_eq!(Container::sniff(&[0xFF, 0xF8, 0x69, 0x08]), None);
+        assert_eq!(Container::sniff(b"OggS"), None);
+        assert_eq!(
+            Container::sniff(b"\0\0\0\x20ftypM4A "),
+            Some(Container::Mp4)
+        );
+    }
+
+    #[test]
+    fn test_get_fields_ok() -> Result<()> {
+        let file = File::open("res/test.ncm")?;
+        let info = Ncmdump::from_reader(file)?.get_info()?;
+        let fields = get_fields(&info, &TagOptions::default());
+        let get = |field| {
+            fields
+                .iter()
+                .find(|(f, _)| *f == field)
+                .map(|(_, values)| values.clone())
+        };
+        assert_eq!(get(Field::Title), Some(vec!["寒鸦少年".to_string()]));
+        assert_eq!(get(Field::AlbumArtist), Some(vec!["华晨宇".to_string()]));
+        assert_eq!(
+            get(Field::Subtitle),
+            Some(vec!["电视剧《斗破苍穹》主题曲".to_string()]),
+        );
+        assert_eq!(
+            get(Field::Website),
+            Some(vec!["https://music.163.com/song?id=1305366556".to_string()]),
+        );
+        assert_eq!(get(Field::NeteaseId), Some(vec!["1305366556".to_string()]));
+        assert_eq!(
+            get(Field::NeteaseAlbumId),
+            Some(vec!["72706788".to_string()])
+        );
+
+        let info = NcmInfo {
+            artist: Vec::new(),
+            id: None,
+            album_id: None,
+            alias: Some(vec![String::new()]),
+            ..info
+        };
+        let fields = get_fields(&info, &TagOptions::default());
+        let fields = fields.iter().map(|(f, _)| *f).collect::<Vec<_>>();
+        assert_eq!(fields, [Field::Title, Field::Album, Field::Artist]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_image_mime_type_ok() {
+        assert_eq!(get_image_mime_type(&fixture::IMAGE), "image/jpeg");
+        assert_eq!(
+            get_image_mime_type(b"\x89PNG\r\n\x1a\n\0\0\0\0"),
+            "image/png"
+        );
+        assert_eq!(get_image_mime_type(b"GIF8"), "image/*");
+    }
+
+    #[test]
+    fn test_tag_options_writes_ok() {
+        let options = TagOptions::default();
+        assert!(options.writes(true, b"value"));
+        assert!(options.writes(false, b""));
+        assert!(!options.writes_extra(true));
+        assert!(!options.removes_extras());
+        assert!(!options.keeps(true));
+
+        let options = TagOptions {
+            policy: TagPolicy::FillMissing,
+            ..Default::default()
+        };
+        assert!(options.writes(false, b"value"));
+        assert!(!options.writes(true, b"value"));
+        assert!(!options.writes(false, b""));
+
+        let options = TagOptions {
+            keep_extras: false,
+            ..Default::default()
+        };
+        assert!(options.writes_extra(true));
+        assert!(options.removes_extras());
+
+        let options = TagOptions {
+            policy: TagPolicy::Keep,
+            ..Default::default()
+        };
+        assert!(options.keeps(true));
+        assert!(!options.keeps(false));
+    }
+
+    #[test]
+    fn test_is_extra_comment_ok() {
+        assert!(is_extra_comment("REPLAYGAIN_TRACK_GAIN"));
+        assert!(is_extra_comment("replaygain_album_peak"));
+        assert!(is_extra_comment("Lyrics"));
+        assert!(!is_extra_comment("TITLE"));
+    }
+
+    #[test]
+    fn test_write_tagged_ok() -> Result<()> {
+        let file = File::open("res/test.ncm")?;
+        let mut ncm = Ncmdump::from_reader(file)?;
+        let mut output = Vec::new();
+        let size = write_tagged(&mut ncm, &mut output, &TagOptions::default())?;
+        assert_eq!(size, output.len() as u64);
+
+        let tag = metaflac::Tag::read_from(&mut output.as_slice())?;
+        let comments = tag.vorbis_comments().unwrap();
+        let info = ncm.get_info()?;
+        assert_eq!(comments.title(), Some(&vec![info.name]));
+        assert_eq!(comments.album(), Some(&vec![info.album]));
+        assert!(comments.get("DESCRIPTION").is_some());
+        assert_eq!(tag.pictures().count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_cover_ok() -> Result<()> {
+        let file = File::open("res/test.ncm")?;
+        let mut ncm = Ncmdump::from_reader(file)?;
+        let data = ncm.get_data()?;
+        let tags = ncm.get_tags()?;
+        let options = TagOptions {
+            cover: CoverOptions {
+                max_size: Some(100),
+                quality: Some(80),
+                ..Default::default()
+            },
+            // The music embeds its own cover.
+            keep_extras: false,
+            ..Default::default()
+        };
+        let output = apply(&data, &tags, &options)?;
+        let tag = metaflac::Tag::read_from(&mut output.as_slice())?;
+        let picture = tag.pictures().next().unwrap();
+        assert_eq!(picture.mime_type, "image/jpeg");
+        assert_eq!(
+            (picture.width, picture.height, picture.depth),
+            (100, 100, 24)
+        );
+
+        let options = TagOptions {
+            cover: CoverOptions {
+                embed: false,
+                ..Default::default()
+            },
+            keep_extras: false,
+            ..Default::default()
+        };
+        let output = apply(&data, &tags, &options)?;
+        let tag = metaflac::Tag::read_from(&mut output.as_slice())?;
+        let embedded = metaflac::Tag::read_from(&mut data.as_slice())?;
+        let pictures = |tag: &metaflac::Tag| tag.pictures().cloned().collect::<Vec<_>>();
+        assert_eq!(pictures(&tag), pictures(&embedded));
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_from_name_ok() {
+        for field in FIELDS {
+            assert_eq!(Field::from_name(field.name()), Some(field));
+        }
+        assert_eq!(Field::from_name("name"), None);
+    }
+
+    #[test]
+    fn test_read_fields_err() {
+        let result = read_fields(b"RIFF....");
+        assert!(matches!(result, Err(Errors::InvalidFileType)));
+    }
+
+    #[test]
+    fn test_apply_unknown_err() {
+        let result = apply(b"RIFF....", &Tags::default(), &TagOptions::default());
+        assert!(matches!(result, Err(Errors::InvalidFileType)));
+    }
+}
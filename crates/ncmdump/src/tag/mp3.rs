@@ -0,0 +1,557 @@
+use std::io::Cursor;
+
+use id3::frame::{
+    Comment, ExtendedText, Picture, PictureType, SynchronisedLyrics, SynchronisedLyricsType,
+    Timestamp, TimestampFormat,
+};
+use id3::{Encoding, Frame, TagLike, Version};
+
+use super::map::is_id3_key;
+use super::{
+    collect_fields, get_id3_frames, get_image_mime_type, is_extra_comment, Container, Field,
+    Id3Version, TagOptions, Tags,
+};
+use crate::error::{Errors, Result};
+
+/// The frames which only exist in id3v2.4, they're removed when writing id3v2.3.
+const V24_FRAMES: [&str; 16] = [
+    "ASPI", "EQU2", "RVA2", "SEEK", "SIGN", "TDEN", "TDRL", "TDTG", "TIPL", "TMCL", "TMOO", "TPRO",
+    "TSOA", "TSOP", "TSOT", "TSST",
+];
+
+/// The length of id3v1 tag.
+const V1_LENGTH: usize = 128;
+
+/// Write the id3 tag into the mp3 music by the policy of the options.
+///
+/// With the `Keep` policy, the music is left untouched if it already has frames. With
+/// the default `Overwrite` policy, the mapped frames are replaced, and if the extras
+/// aren't kept, the embedded lyrics and ReplayGain are removed and the embedded cover
+/// is replaced. With the `FillMissing` policy, only the missing frames are written.
+/// The other embedded frames, and the embedded cover and lyrics, are kept otherwise.
+pub(crate) fn write(data: &[u8], tags: &Tags, options: &TagOptions) -> Result<Vec<u8>> {
+    let mut tag = id3::Tag::read_from2(Cursor::new(data)).unwrap_or_else(|_| id3::Tag::new());
+    if options.keeps(tag.frames().next().is_some()) {
+        return Ok(data.to_vec());
+    }
+    if options.removes_extras() {
+        tag.remove_all_lyrics();
+        tag.remove_all_synchronised_lyrics();
+        tag.remove("RVA2");
+        let descriptions = tag
+            .extended_texts()
+            .filter(|text| is_extra_comment(&text.description))
+            .map(|text| text.description.to_owned())
+            .collect::<Vec<_>>();
+        for description in descriptions {
+            tag.remove_extended_text(Some(&description), None);
+        }
+    }
+    // The multiple values are separated by null in id3v2.4.
+    let separator = match options.id3_version {
+        Id3Version::V23 => options.separator.as_str(),
+        Id3Version::V24 => "\0",
+    };
+    if let Some(info) = &tags.info {
+        for (key, values) in get_id3_frames(info, options) {
+            // The invalid keys of the unchecked tag map are skipped.
+            if !is_id3_key(&key) {
+                continue;
+            }
+            let frame = get_frame(&key, &values, separator);
+            if options.writes(is_embedded(&tag, &frame), values.concat().as_bytes()) {
+                tag.add_frame(frame);
+            }
+        }
+    }
+    if let Some(comment) = tags.comment.as_ref().filter(|_| options.comment) {
+        if options.writes(tag.comments().next().is_some(), comment.as_bytes()) {
+            tag.add_frame(Comment {
+                lang: "XXX".to_string(),
+                description: "".to_string(),
+                text: comment.to_string(),
+            });
+        }
+    }
+    let lyrics = tag.lyrics().next().is_some() || tag.synchronised_lyrics().next().is_some();
+    if !tags.lyrics.is_empty() && options.writes_extra(lyrics) {
+        tag.remove_all_lyrics();
+        tag.remove_all_synchronised_lyrics();
+        for lyrics in &tags.lyrics {
+            let (lang, description) = lyrics.id3_language();
+            // The frames are told apart by the language, only the first lyrics are kept.
+            // The synchronised lyrics are told apart by the language code only.
+            let synced = tag
+                .synchronised_lyrics()
+                .any(|embedded| embedded.lang == lang);
+            if options.synced_lyrics && lyrics.is_synced() && !synced {
+                tag.add_frame(SynchronisedLyrics {
+                    lang: lang.to_owned(),
+                    timestamp_format: TimestampFormat::Ms,
+                    content_type: SynchronisedLyricsType::Lyrics,
+                    description: description.to_owned(),
+                    content: lyrics
+                        .lines
+                        .iter()
+                        .map(|line| (line.time.unwrap_or_default(), line.text.to_owned()))
+                        .collect(),
+                });
+            }
+            let embedded = tag
+                .lyrics()
+                .any(|embedded| embedded.lang == lang && embedded.description == description);
+            if !embedded {
+                tag.add_frame(id3::frame::Lyrics {
+                    lang,
+                    description,
+                    text: lyrics.to_text(),
+                });
+            }
+        }
+    }
+    if !tags.image.is_empty() && options.writes_extra(tag.pictures().next().is_some()) {
+        tag.remove_all_pictures();
+        tag.add_frame(Picture {
+            mime_type: get_image_mime_type(&tags.image).to_owned(),
+            picture_type: PictureType::CoverFront,
+            description: "".to_string(),
+            data: tags.image.to_vec(),
+        });
+    }
+
+    let (tag, version) = match options.id3_version {
+        Id3Version::V23 => (downgrade(&tag, &options.separator), Version::Id3v23),
+        Id3Version::V24 => (tag, Version::Id3v24),
+    };
+    let mut cursor = Cursor::new(data.to_vec());
+    tag.write_to_file(&mut cursor, version)
+        .map_err(|source| Errors::Tag {
+            container: Container::Mp3,
+            source: Box::new(source),
+        })?;
+    let mut output = cursor.into_inner();
+    if options.id3v1 {
+        if output.len() >= V1_LENGTH && output[output.len() - V1_LENGTH..].starts_with(b"TAG") {
+            output.truncate(output.len() - V1_LENGTH);
+        }
+        output.extend_from_slice(&build_v1(&tag, &options.separator));
+    }
+    Ok(output)
+}
+
+/// Read the fields from the id3 tag of the mp3 music, the multiple values are separated
+/// by null.
+pub(crate) fn read(data: &[u8]) -> Result<Vec<(Field, Vec<String>)>> {
+    let tag = id3::Tag::read_from2(Cursor::new(data)).unwrap_or_else(|_| id3::Tag::new());
+    Ok(collect_fields(|field| {
+        let key = field.id3_key();
+        let value = match key.strip_prefix("TXXX:") {
+            Some(description) => tag
+                .extended_texts()
+                .find(|text| text.description == description)
+                .map(|text| text.value.as_str()),
+            None => tag
+                .get(key)
+                .and_then(|frame| frame.content().text().or(frame.content().link())),
+        };
+        value
+            .map(|value| value.split('\0').map(str::to_string).collect())
+            .unwrap_or_default()
+    }))
+}
+
+/// Get the id3 frame by the key, the key is the id of frame or `TXXX:description`.
+fn get_frame(key: &str, values: &[String], separator: &str) -> Frame {
+    match key.strip_prefix("TXXX:") {
+        Some(description) => Frame::from(ExtendedText {
+            description: description.to_string(),
+            value: values.join(separator),
+        }),
+        // The link frame can only contain a single url.
+        None if key.starts_with('W') => {
+            Frame::link(key, values.first().cloned().unwrap_or_default())
+        }
+        None => Frame::text(key, values.join(separator)),
+    }
+}
+
+/// Convert the tag into id3v2.3, the dates are split into the v2.3 frames, the v2.4
+/// only frames are removed and the multiple values are joined with the separator.
+fn downgrade(tag: &id3::Tag, separator: &str) -> id3::Tag {
+    let mut downgraded = id3::Tag::with_version(Version::Id3v23);
+    for frame in tag.frames() {
+        match frame.id() {
+            "TDRC" => {
+                let Some(date) = frame.content().text().and_then(|text| text.parse().ok()) else {
+                    continue;
+                };
+                let Timestamp {
+                    year,
+                    month,
+                    day,
+                    hour,
+                    minute,
+                    ..
+                } = date;
+                downgraded.add_frame(Frame::text("TYER", format!("{:04}", year)));
+                if let (Some(month), Some(day)) = (month, day) {
+                    downgraded.add_frame(Frame::text("TDAT", format!("{:02}{:02}", day, month)));
+                }
+                if let (Some(hour), Some(minute)) = (hour, minute) {
+                    downgraded.add_frame(Frame::text("TIME", format!("{:02}{:02}", hour, minute)));
+                }
+            }
+            "TDOR" => {
+                if let Some(date) = frame.content().text().and_then(|text| text.parse().ok()) {
+                    let Timestamp { year, .. } = date;
+                    downgraded.add_frame(Frame::text("TORY", format!("{:04}", year)));
+                }
+            }
+            id if V24_FRAMES.contains(&id) => {}
+            id => {
+                let content = frame.content();
+                let frame = match (content.text(), content.extended_text()) {
+                    (Some(text), _) => Frame::text(id, text.replace('\0', separator)),
+                    (_, Some(text)) => Frame::from(ExtendedText {
+                        description: text.description.to_owned(),
+                        value: text.value.replace('\0', separator),
+                    }),
+                    _ => frame.clone(),
+                };
+                // The utf-8 and utf-16be encodings are not supported by id3v2.3.
+                let frame = match frame.encoding() {
+                    Some(Encoding::UTF8 | Encoding::UTF16BE) => frame.set_encoding(None),
+                    _ => frame,
+                };
+                downgraded.add_frame(frame);
+            }
+        }
+    }
+    downgraded
+}
+
+/// Build the id3v1.1 tag, the texts are encoded in latin-1 and truncated.
+fn build_v1(tag: &id3::Tag, separator: &str) -> [u8; V1_LENGTH] {
+    let text = |id| {
+        tag.get(id)
+            .and_then(|frame| frame.content().text())
+            .unwrap_or_default()
+            .replace('\0', separator)
+    };
+    let year = tag
+        .year()
+        .or_else(|| tag.date_recorded().map(|date| date.year))
+        .map(|year| year.to_string())
+        .unwrap_or_default();
+    let mut v1 = [0; V1_LENGTH];
+    v1[..3].copy_from_slice(b"TAG");
+    write_latin1(&mut v1[3..33], &text("TIT2"));
+    write_latin1(&mut v1[33..63], &text("TPE1"));
+    write_latin1(&mut v1[63..93], &text("TALB"));
+    write_latin1(&mut v1[93..97], &year);
+    // The comment is left empty, it's followed by a zero byte and the track number.
+    v1[126] = tag
+        .track()
+        .and_then(|track| u8::try_from(track).ok())
+        .unwrap_or_default();
+    // The genre is unknown.
+    v1[127] = 0xFF;
+    v1
+}
+
+/// Write the text into the field in latin-1, the other characters are replaced by `?`.
+fn write_latin1(field: &mut [u8], text: &str) {
+    let bytes = text.chars().map(|c| u8::try_from(c).unwrap_or(b'?'));
+    for (byte, value) in field.iter_mut().zip(bytes) {
+        *byte = value;
+    }
+}
+
+/// Check if the frame is embedded, the extended texts are told apart by the description.
+fn is_embedded(tag: &id3::Tag, frame: &Frame) -> bool {
+    match frame.content().extended_text() {
+        Some(text) => tag
+            .extended_texts()
+            .any(|embedded| embedded.description == text.description),
+        None => tag.get(frame.id()).is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::tag::fixture::{self, get_info};
+    use crate::tag::{Lyrics, TagPolicy};
+
+    #[test]
+    fn test_write_mp3_ok() -> Result<()> {
+        let mut data = Vec::new();
+        id3::Tag::new().write_to(&mut data, Version::Id3v24)?;
+        data.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        let tags = Tags {
+            lyrics: vec![Lyrics::parse("[la:eng]\n[00:01.00]La\n[00:02.00]Di")],
+            ..fixture::get_tags()
+        };
+        let output = write(&data, &tags, &TagOptions::default())?;
+        assert_eq!(output[output.len() - 4..], [0xFF, 0xFB, 0x90, 0x00]);
+
+        let tag = id3::Tag::read_from2(Cursor::new(&output))?;
+        assert_eq!(tag.title(), Some("Golden Hour"));
+        assert_eq!(tag.artists(), Some(vec!["JVKE", "Other"]));
+        assert_eq!(tag.album_artist(), Some("JVKE"));
+        assert_eq!(
+            tag.get("TIT3").and_then(|f| f.content().text()),
+            Some("Alias")
+        );
+        assert_eq!(
+            tag.get("WOAF").and_then(|f| f.content().link()),
+            Some("https://music.163.com/song?id=1"),
+        );
+        let texts = tag
+            .extended_texts()
+            .map(|text| (text.description.as_str(), text.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(texts, [("NETEASE_ID", "1"), ("NETEASE_ALBUMID", "3")]);
+        assert_eq!(tag.comments().count(), 1);
+        let picture = tag.pictures().next().unwrap();
+        assert_eq!(picture.mime_type, "image/jpeg");
+        let lyrics = tag.lyrics().next().unwrap();
+        assert_eq!(
+            (lyrics.lang.as_str(), lyrics.text.as_str()),
+            ("eng", "La\nDi")
+        );
+        assert_eq!(tag.synchronised_lyrics().count(), 0);
+
+        let fields = read(&output)?;
+        assert_eq!(
+            fields[2],
+            (Field::Artist, vec!["JVKE".to_string(), "Other".to_string()])
+        );
+        assert!(fields.contains(&(
+            Field::Website,
+            vec!["https://music.163.com/song?id=1".into()]
+        )));
+        assert!(fields.contains(&(Field::NeteaseAlbumId, vec!["3".to_string()])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_untagged_mp3_ok() -> Result<()> {
+        let data = [0xFF, 0xFB, 0x90, 0x00].repeat(4);
+        let tags = Tags {
+            info: Some(get_info()),
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &TagOptions::default())?;
+        assert_eq!(output[..3], *b"ID3");
+        assert!(output.ends_with(&data));
+        let tag = id3::Tag::read_from2(Cursor::new(&output))?;
+        assert_eq!(tag.title(), Some("Golden Hour"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_mp3_synced_lyrics_ok() -> Result<()> {
+        let mut data = Vec::new();
+        id3::Tag::new().write_to(&mut data, Version::Id3v24)?;
+        let tags = Tags {
+            lyrics: vec![
+                Lyrics::parse("[00:01.00]La\n[00:02.00]Di"),
+                Lyrics::parse("[la:Chinese]\n[00:01.00]啦"),
+                Lyrics::parse("[la:chi]\n[00:01.00]啦"),
+                Lyrics::parse("Duplicated"),
+            ],
+            ..Default::default()
+        };
+        let options = TagOptions {
+            synced_lyrics: true,
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &options)?;
+        let tag = id3::Tag::read_from2(Cursor::new(&output))?;
+        let lyrics = tag
+            .lyrics()
+            .map(|l| (l.lang.as_str(), l.description.as_str(), l.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lyrics,
+            [
+                ("XXX", "", "La\nDi"),
+                ("XXX", "Chinese", "啦"),
+                ("chi", "", "啦")
+            ]
+        );
+        let synced = tag.synchronised_lyrics().collect::<Vec<_>>();
+        assert_eq!(synced.len(), 2);
+        assert_eq!(synced[0].timestamp_format, TimestampFormat::Ms);
+        assert_eq!(
+            synced[0].content,
+            [(1000, "La".to_string()), (2000, "Di".to_string())]
+        );
+        assert_eq!(synced[1].lang, "chi");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_mp3_fill_missing_ok() -> Result<()> {
+        let mut tag = id3::Tag::new();
+        tag.set_title("Old");
+        let mut data = Vec::new();
+        tag.write_to(&mut data, Version::Id3v24)?;
+        let mut info = get_info();
+        info.album = String::new();
+        let tags = Tags {
+            info: Some(info),
+            ..Default::default()
+        };
+        let options = TagOptions {
+            policy: TagPolicy::FillMissing,
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &options)?;
+        let tag = id3::Tag::read_from2(Cursor::new(&output))?;
+        assert_eq!(tag.title(), Some("Old"));
+        assert_eq!(tag.artists(), Some(vec!["JVKE", "Other"]));
+        assert_eq!(tag.album(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_mp3_tag_policy_ok() -> Result<()> {
+        let mut tag = id3::Tag::new();
+        tag.set_title("Old");
+        tag.add_frame(ExtendedText {
+            description: "REPLAYGAIN_TRACK_GAIN".into(),
+            value: "-1.00 dB".into(),
+        });
+        tag.add_frame(id3::frame::Lyrics {
+            lang: "eng".into(),
+            description: "".into(),
+            text: "la".into(),
+        });
+        tag.add_frame(Picture {
+            mime_type: "image/png".into(),
+            picture_type: PictureType::Other,
+            description: "".into(),
+            data: b"old".to_vec(),
+        });
+        let mut data = Vec::new();
+        tag.write_to(&mut data, Version::Id3v24)?;
+        let tags = fixture::get_tags();
+        let [output, stripped] = fixture::write_policies(&data, &tags, write)?;
+
+        let tag = id3::Tag::read_from2(Cursor::new(output))?;
+        assert_eq!(tag.title(), Some("Golden Hour"));
+        assert_eq!(tag.lyrics().count(), 1);
+        assert_eq!(tag.extended_texts().count(), 3);
+        assert_eq!(
+            tag.pictures().map(|p| &p.data[..]).collect::<Vec<_>>(),
+            [b"old"]
+        );
+
+        let tag = id3::Tag::read_from2(Cursor::new(stripped))?;
+        assert_eq!(tag.lyrics().count(), 0);
+        assert_eq!(tag.extended_texts().count(), 2);
+        assert_eq!(tag.pictures().count(), 1);
+        assert_eq!(tag.pictures().next().unwrap().data, tags.image);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_mp3_no_comment_ok() -> Result<()> {
+        let mut data = Vec::new();
+        id3::Tag::new().write_to(&mut data, Version::Id3v24)?;
+        let tags = Tags {
+            comment: Some("163 key(Don't modify):abc".into()),
+            ..Default::default()
+        };
+        let options = TagOptions {
+            comment: false,
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &options)?;
+        let tag = id3::Tag::read_from2(Cursor::new(&output))?;
+        assert_eq!(tag.comments().count(), 0);
+        assert_eq!(tag.title(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_mp3_v23_ok() -> Result<()> {
+        let mut tag = id3::Tag::new();
+        tag.set_date_recorded("2022-09-15T12:30".parse()?);
+        tag.set_original_date_released("2021".parse()?);
+        tag.add_frame(Frame::text("TSOP", "Sort"));
+        tag.add_frame(
+            Frame::from(ExtendedText {
+                description: "KEY".into(),
+                value: "Value".into(),
+            })
+            .set_encoding(Some(Encoding::UTF8)),
+        );
+        let mut data = Vec::new();
+        tag.write_to(&mut data, Version::Id3v24)?;
+        let tags = Tags {
+            info: Some(get_info()),
+            ..Default::default()
+        };
+        let options = TagOptions {
+            separator: "; ".into(),
+            id3_version: Id3Version::V23,
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &options)?;
+        let tag = id3::Tag::read_from2(Cursor::new(&output))?;
+        assert_eq!(tag.version(), Version::Id3v23);
+        assert_eq!(tag.artist(), Some("JVKE; Other"));
+        assert_eq!(tag.year(), Some(2022));
+        let text = |id| tag.get(id).and_then(|f| f.content().text());
+        assert_eq!(text("TDAT"), Some("1509"));
+        assert_eq!(text("TIME"), Some("1230"));
+        assert_eq!(text("TORY"), Some("2021"));
+        assert_eq!(text("TDRC"), None);
+        assert_eq!(text("TSOP"), None);
+        assert_eq!(tag.extended_texts().count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_mp3_id3v1_ok() -> Result<()> {
+        let mut data = Vec::new();
+        let mut tag = id3::Tag::new();
+        tag.set_year(2022);
+        tag.set_track(7);
+        tag.write_to(&mut data, Version::Id3v24)?;
+        data.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        let mut info = get_info();
+        info.name = "Golden Hour ☀".into();
+        let tags = Tags {
+            info: Some(info),
+            ..Default::default()
+        };
+        let options = TagOptions {
+            id3v1: true,
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &options)?;
+        // The existing id3v1 tag is replaced.
+        let output = write(&output, &tags, &options)?;
+        let v1 = &output[output.len() - V1_LENGTH..];
+        assert_eq!(
+            output[output.len() - V1_LENGTH - 4..][..4],
+            [0xFF, 0xFB, 0x90, 0x00]
+        );
+        assert_eq!(v1[..3], *b"TAG");
+        assert_eq!(
+            v1[3..33],
+            *b"Golden Hour ?\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0"
+        );
+        assert_eq!(v1[33..43], *b"JVKE/Other");
+        assert_eq!(v1[63..74], *b"Golden Hour");
+        assert_eq!(v1[93..97], *b"2022");
+        assert_eq!(v1[125..], [0, 7, 0xFF]);
+        Ok(())
+    }
+}
@@ -0,0 +1,691 @@
+use super::{
+    collect_fields, get_fields, get_image_mime_type, is_extra_comment, Container, Field, Lyrics,
+    MalformedError, TagOptions, TagPolicy, Tags,
+};
+use crate::error::{Errors, Result, Section};
+
+/// The boxes which contain the sample tables.
+const TABLE_CONTAINERS: [&[u8; 4]; 4] = [b"trak", b"mdia", b"minf", b"stbl"];
+const DATA_UTF8: u32 = 1;
+/// The mean of the freeform items.
+const FREEFORM_MEAN: &[u8] = b"com.apple.iTunes";
+
+/// The item of `ilst`, the first is the key to tell the items apart, the second is the
+/// raw item box.
+type Item = (Vec<u8>, Vec<u8>);
+
+/// The box of mp4, the offsets are relative to the parent.
+#[derive(Clone, Copy, Debug)]
+struct Atom {
+    kind: [u8; 4],
+    start: usize,
+    header: usize,
+    end: usize,
+}
+
+impl Atom {
+    fn body<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.start + self.header..self.end]
+    }
+
+    fn raw<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.start..self.end]
+    }
+}
+
+/// Parse the boxes in the data.
+fn parse_atoms(data: &[u8]) -> Result<Vec<Atom>> {
+    let mut atoms = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let rest = &data[start..];
+        let header = rest.get(..8).ok_or(Errors::Truncated {
+            section: Section::Audio,
+            offset: data.len() as u64,
+        })?;
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let (size, header) = match size {
+            0 => (rest.len() as u64, 8),
+            1 => {
+                let large = rest.get(8..16).ok_or(Errors::Truncated {
+                    section: Section::Audio,
+                    offset: data.len() as u64,
+                })?;
+                (u64::from_be_bytes(large.try_into().unwrap_or_default()), 16)
+            }
+            size => (size, 8),
+        };
+        if size < header as u64 {
+            return Err(tag_error("Invalid box size"));
+        }
+        if size > rest.len() as u64 {
+            return Err(Errors::Truncated {
+                section: Section::Audio,
+                offset: data.len() as u64,
+            });
+        }
+        atoms.push(Atom {
+            kind,
+            start,
+            header,
+            end: start + size as usize,
+        });
+        start += size as usize;
+    }
+    Ok(atoms)
+}
+
+/// Build the box with the kind and body.
+fn build_atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut atom = Vec::with_capacity(body.len() + 8);
+    atom.extend_from_slice(&(body.len() as u32 + 8).to_be_bytes());
+    atom.extend_from_slice(kind);
+    atom.extend_from_slice(body);
+    atom
+}
+
+/// Build the item of `ilst` with a `data` box.
+fn build_item(kind: &[u8; 4], data_type: u32, value: &[u8]) -> Item {
+    let data = build_data(data_type, value);
+    (kind.to_vec(), build_atom(kind, &data))
+}
+
+/// Build the freeform `----` item of `ilst` with the name.
+fn build_freeform(name: &str, value: &[u8]) -> Item {
+    let mut mean = vec![0; 4];
+    mean.extend_from_slice(FREEFORM_MEAN);
+    let mut body = build_atom(b"mean", &mean);
+    let mut name_body = vec![0; 4];
+    name_body.extend_from_slice(name.as_bytes());
+    body.extend_from_slice(&build_atom(b"name", &name_body));
+    body.extend_from_slice(&build_data(DATA_UTF8, value));
+    (item_key(*b"----", &body), build_atom(b"----", &body))
+}
+
+fn build_data(data_type: u32, value: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(value.len() + 8);
+    data.extend_from_slice(&data_type.to_be_bytes());
+    // The locale.
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(value);
+    build_atom(b"data", &data)
+}
+
+/// Get the key of the item, the freeform items are told apart by the name.
+fn item_key(kind: [u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut key = kind.to_vec();
+    if &kind == b"----" {
+        let atoms = parse_atoms(body).unwrap_or_default();
+        if let Some(name) = atoms.iter().find(|atom| &atom.kind == b"name") {
+            key.extend_from_slice(name.body(body).get(4..).unwrap_or_default());
+        }
+    }
+    key
+}
+
+/// Replace the child boxes of the container, or append them if they are missing.
+///
+/// The function is called with the old child, or none if the child is missing.
+fn replace_child<F>(body: &[u8], kind: &[u8; 4], f: F) -> Result<Vec<u8>>
+where
+    F: FnOnce(Option<&[u8]>) -> Result<Vec<u8>>,
+{
+    let atoms = parse_atoms(body)?;
+    let mut output = Vec::with_capacity(body.len());
+    let mut f = Some(f);
+    for atom in &atoms {
+        match &atom.kind == kind {
+            true => match f.take() {
+                Some(f) => output.extend_from_slice(&build_atom(kind, &f(Some(atom.body(body)))?)),
+                None => continue,
+            },
+            false => output.extend_from_slice(atom.raw(body)),
+        }
+    }
+    if let Some(f) = f {
+        output.extend_from_slice(&build_atom(kind, &f(None)?));
+    }
+    Ok(output)
+}
+
+/// Get the kind of the item of the field, the other fields are the freeform items.
+fn field_kind(field: Field) -> Option<&'static [u8; 4]> {
+    match field {
+        Field::Title => Some(b"\xA9nam"),
+        Field::Album => Some(b"\xA9alb"),
+        Field::Artist => Some(b"\xA9ART"),
+        Field::AlbumArtist => Some(b"aART"),
+        _ => None,
+    }
+}
+
+/// Build the items of `ilst` from the tags.
+fn build_items(tags: &Tags, options: &TagOptions) -> Vec<Item> {
+    let mut items = Vec::new();
+    let mut push = |item: Item, value: &[u8]| {
+        // The empty values are skipped when only the missing items are filled.
+        if options.writes(false, value) {
+            items.push(item);
+        }
+    };
+    if let Some(info) = &tags.info {
+        for (field, values) in get_fields(info, options) {
+            let value = values.join(&options.separator);
+            let value = value.as_bytes();
+            let item = match field_kind(field) {
+                Some(kind) => build_item(kind, DATA_UTF8, value),
+                None => build_freeform(field.vorbis_key(), value),
+            };
+            push(item, value);
+        }
+    }
+    if let Some(comment) = tags.comment.as_ref().filter(|_| options.comment) {
+        push(
+            build_item(b"\xA9cmt", DATA_UTF8, comment.as_bytes()),
+            comment.as_bytes(),
+        );
+    }
+    if !tags.lyrics.is_empty() {
+        let lyrics = tags.lyrics.iter().map(Lyrics::to_text).collect::<Vec<_>>();
+        let lyrics = lyrics.join("\n\n");
+        push(
+            build_item(b"\xA9lyr", DATA_UTF8, lyrics.as_bytes()),
+            lyrics.as_bytes(),
+        );
+    }
+    if !tags.image.is_empty() {
+        let data_type = match get_image_mime_type(&tags.image) {
+            "image/png" => 14,
+            "image/bmp" => 27,
+            _ => 13,
+        };
+        push(build_item(b"covr", data_type, &tags.image), &tags.image);
+    }
+    items
+}
+
+/// Rewrite the `ilst` box, the items which are not set are kept.
+fn rewrite_ilst(ilst: Option<&[u8]>, items: &[Item], options: &TagOptions) -> Result<Vec<u8>> {
+    let ilst = ilst.unwrap_or_default();
+    let keys = parse_atoms(ilst)?
+        .into_iter()
+        .map(|atom| (item_key(atom.kind, atom.body(ilst)), atom))
+        .collect::<Vec<_>>();
+    if options.keeps(!keys.is_empty()) {
+        return Ok(ilst.to_vec());
+    }
+    let embedded = |key: &[u8]| keys.iter().any(|(k, _)| k == key);
+    let items = items
+        .iter()
+        .filter(|(key, _)| match key.as_slice() {
+            b"covr" | b"\xA9lyr" => options.writes_extra(embedded(key)),
+            // The empty values are already skipped when the items are built.
+            _ => !(options.policy == TagPolicy::FillMissing && embedded(key)),
+        })
+        .collect::<Vec<_>>();
+    let mut output = Vec::new();
+    for (key, atom) in &keys {
+        let replaced = items.iter().any(|(k, _)| k == key);
+        let removed = options.removes_extras() && is_extra_item(key);
+        if !(replaced || removed) {
+            output.extend_from_slice(atom.raw(ilst));
+        }
+    }
+    for (_, item) in items {
+        output.extend_from_slice(item);
+    }
+    Ok(output)
+}
+
+/// Check if the item is the ReplayGain or lyrics.
+fn is_extra_item(key: &[u8]) -> bool {
+    match key.strip_prefix(b"----") {
+        Some(name) => is_extra_comment(&String::from_utf8_lossy(name)),
+        None => key == b"\xA9lyr",
+    }
+}
+
+/// Rewrite the `meta` box, it's a full box with the `hdlr` and `ilst` children.
+fn rewrite_meta(meta: Option<&[u8]>, items: &[Item], options: &TagOptions) -> Result<Vec<u8>> {
+    let Some(meta) = meta else {
+        let mut output = vec![0; 4];
+        let mut hdlr = vec![0; 8];
+        hdlr.extend_from_slice(b"mdirappl");
+        hdlr.extend_from_slice(&[0; 9]);
+        output.extend_from_slice(&build_atom(b"hdlr", &hdlr));
+        output.extend_from_slice(&build_atom(b"ilst", &rewrite_ilst(None, items, options)?));
+        return Ok(output);
+    };
+    let (version, children) = meta
+        .split_at_checked(4)
+        .ok_or(tag_error("Invalid meta box"))?;
+    let mut output = version.to_vec();
+    output.extend(replace_child(children, b"ilst", |ilst| {
+        rewrite_ilst(ilst, items, options)
+    })?);
+    Ok(output)
+}
+
+/// Add the delta to the chunk offsets after the threshold in the sample tables.
+fn patch_offsets(data: &mut [u8], threshold: u64, delta: i64) -> Result<()> {
+    for atom in parse_atoms(data)? {
+        let body = &mut data[atom.start + atom.header..atom.end];
+        if TABLE_CONTAINERS.contains(&&atom.kind) {
+            patch_offsets(body, threshold, delta)?;
+            continue;
+        }
+        let width = match &atom.kind {
+            b"stco" => 4,
+            b"co64" => 8,
+            _ => continue,
+        };
+        let count = body
+            .get(4..8)
+            .map(|count| u32::from_be_bytes([count[0], count[1], count[2], count[3]]) as usize)
+            .ok_or(tag_error("Invalid chunk offset box"))?;
+        let entries = body
+            .get_mut(8..8 + count * width)
+            .ok_or(tag_error("Invalid chunk offset box"))?;
+        for entry in entries.chunks_exact_mut(width) {
+            let offset = match width {
+                4 => u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]) as u64,
+                _ => u64::from_be_bytes(entry.try_into().unwrap_or_default()),
+            };
+            if offset < threshold {
+                continue;
+            }
+            let offset = offset
+                .checked_add_signed(delta)
+                .ok_or(tag_error("Chunk offset overflow"))?;
+            match width {
+                4 => entry.copy_from_slice(
+                    &u32::try_from(offset)
+                        .map_err(|_| tag_error("Chunk offset overflow"))?
+                        .to_be_bytes(),
+                ),
+                _ => entry.copy_from_slice(&offset.to_be_bytes()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read the fields from the `moov/udta/meta/ilst` items of the mp4 music, the values
+/// are the text data of the items.
+pub(crate) fn read(data: &[u8]) -> Result<Vec<(Field, Vec<String>)>> {
+    let find = |body: &[u8], kind: &[u8; 4]| -> Result<Option<Atom>> {
+        Ok(parse_atoms(body)?
+            .into_iter()
+            .find(|atom| &atom.kind == kind))
+    };
+    let moov = find(data, b"moov")?.ok_or(tag_error("The moov box is missing"))?;
+    let moov = moov.body(data);
+    let Some(udta) = find(moov, b"udta")? else {
+        return Ok(Vec::new());
+    };
+    let udta = udta.body(moov);
+    let Some(meta) = find(udta, b"meta")? else {
+        return Ok(Vec::new());
+    };
+    let meta = meta
+        .body(udta)
+        .get(4..)
+        .ok_or(tag_error("Invalid meta box"))?;
+    let Some(ilst) = find(meta, b"ilst")? else {
+        return Ok(Vec::new());
+    };
+    let ilst = ilst.body(meta);
+    let mut items = Vec::new();
+    for atom in parse_atoms(ilst)? {
+        let body = atom.body(ilst);
+        let values = parse_atoms(body)?
+            .into_iter()
+            .filter(|child| &child.kind == b"data")
+            .filter_map(|child| child.body(body).get(8..))
+            .map(|value| String::from_utf8_lossy(value).to_string())
+            .collect::<Vec<_>>();
+        items.push((item_key(atom.kind, body), values));
+    }
+    Ok(collect_fields(|field| {
+        let key = match field_kind(field) {
+            Some(kind) => kind.to_vec(),
+            None => [b"----", field.vorbis_key().as_bytes()].concat(),
+        };
+        items
+            .iter()
+            .filter(|(k, _)| *k == key)
+            .flat_map(|(_, values)| values.iter().cloned())
+            .collect()
+    }))
+}
+
+/// Write the `ilst` items into the mp4 music by the policy of the options.
+///
+/// With the `Keep` policy, the music is left untouched if it already has items. With
+/// the default `Overwrite` policy, the mapped items are replaced, and if the extras
+/// aren't kept, the embedded lyrics and ReplayGain items are removed and the `covr`
+/// item is replaced. With the `FillMissing` policy, only the missing items are
+/// written. The other items, `covr` and `©lyr` are kept otherwise.
+///
+/// The `moov/udta/meta/ilst` boxes are created if they are missing, and the chunk
+/// offsets are fixed up if the `moov` box is in front of the `mdat` box.
+pub(crate) fn write(data: &[u8], tags: &Tags, options: &TagOptions) -> Result<Vec<u8>> {
+    let atoms = parse_atoms(data)?;
+    let moov = atoms
+        .iter()
+        .find(|atom| &atom.kind == b"moov")
+        .ok_or(tag_error("The moov box is missing"))?;
+    let items = build_items(tags, options);
+    let body = replace_child(moov.body(data), b"udta", |udta| {
+        replace_child(udta.unwrap_or_default(), b"meta", |meta| {
+            rewrite_meta(meta, &items, options)
+        })
+    })?;
+    let mut new_moov = build_atom(b"moov", &body);
+
+    let delta = new_moov.len() as i64 - (moov.end - moov.start) as i64;
+    if delta != 0 {
+        patch_offsets(&mut new_moov[8..], moov.end as u64, delta)?;
+    }
+    let mut output = Vec::with_capacity(data.len() + new_moov.len());
+    output.extend_from_slice(&data[..moov.start]);
+    output.extend_from_slice(&new_moov);
+    output.extend_from_slice(&data[moov.end..]);
+    Ok(output)
+}
+
+fn tag_error(message: &'static str) -> Errors {
+    Errors::Tag {
+        container: Container::Mp4,
+        source: Box::new(MalformedError(message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::tag::fixture::{self, get_tags};
+
+    fn full_atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; 4];
+        data.extend_from_slice(body);
+        build_atom(kind, &data)
+    }
+
+    /// Build the mp4 file with two chunks, the udta box is placed in the moov box.
+    fn build_file(moov_first: bool, udta: Option<Vec<u8>>) -> Vec<u8> {
+        let ftyp = build_atom(b"ftyp", b"M4A \0\0\0\0M4A mp42isom");
+        let mdat = build_atom(b"mdat", b"CHUNK-ONECHUNK-TWO");
+        let build_moov = |mdat_start: usize| {
+            let mut stco = 2u32.to_be_bytes().to_vec();
+            stco.extend_from_slice(&(mdat_start as u32 + 8).to_be_bytes());
+            stco.extend_from_slice(&(mdat_start as u32 + 17).to_be_bytes());
+            let mut co64 = 1u32.to_be_bytes().to_vec();
+            co64.extend_from_slice(&(mdat_start as u64 + 17).to_be_bytes());
+            let mut stbl = full_atom(b"stco", &stco);
+            stbl.extend_from_slice(&full_atom(b"co64", &co64));
+            let stbl = build_atom(b"stbl", &stbl);
+            let trak = build_atom(b"trak", &build_atom(b"mdia", &build_atom(b"minf", &stbl)));
+            let mut moov = full_atom(b"mvhd", &[0; 16]);
+            moov.extend_from_slice(&trak);
+            if let Some(udta) = &udta {
+                moov.extend_from_slice(&build_atom(b"udta", udta));
+            }
+            build_atom(b"moov", &moov)
+        };
+        let mut data = ftyp.clone();
+        if moov_first {
+            let length = build_moov(0).len();
+            data.extend_from_slice(&build_moov(ftyp.len() + length));
+            data.extend_from_slice(&mdat);
+        } else {
+            data.extend_from_slice(&mdat);
+            data.extend_from_slice(&build_moov(ftyp.len()));
+        }
+        data
+    }
+
+    /// Find the box by the path.
+    fn find<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+        let Some((kind, rest)) = path.split_first() else {
+            return Some(data);
+        };
+        let atom = parse_atoms(data)
+            .ok()?
+            .into_iter()
+            .find(|a| &a.kind == *kind)?;
+        let body = atom.body(data);
+        match *kind {
+            b"meta" | b"stco" | b"co64" => find(&body[4..], rest),
+            _ => find(body, rest),
+        }
+    }
+
+    /// Read the chunks by the chunk offsets.
+    fn read_chunks(data: &[u8]) -> Vec<Vec<u8>> {
+        let stbl = find(data, &[b"moov", b"trak", b"mdia", b"minf", b"stbl"]).unwrap();
+        let stco = find(stbl, &[b"stco"]).unwrap();
+        let co64 = find(stbl, &[b"co64"]).unwrap();
+        let mut offsets = stco[4..]
+            .chunks(4)
+            .map(|c| u32::from_be_bytes(c.try_into().unwrap()) as usize)
+            .collect::<Vec<_>>();
+        offsets.push(u64::from_be_bytes(co64[4..].try_into().unwrap()) as usize);
+        offsets
+            .into_iter()
+            .map(|offset| data[offset..offset + 9].to_vec())
+            .collect()
+    }
+
+    fn item_value<'a>(ilst: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+        find(ilst, &[kind, b"data"]).map(|data| &data[8..])
+    }
+
+    #[test]
+    fn test_write_moov_first_ok() -> Result<()> {
+        let data = build_file(true, None);
+        let chunks = [
+            b"CHUNK-ONE".to_vec(),
+            b"CHUNK-TWO".to_vec(),
+            b"CHUNK-TWO".to_vec(),
+        ];
+        assert_eq!(read_chunks(&data), chunks);
+
+        let output = write(&data, &get_tags(), &TagOptions::default())?;
+        assert!(output.len() > data.len());
+        assert_eq!(read_chunks(&output), chunks);
+
+        let meta = find(&output, &[b"moov", b"udta", b"meta"]).unwrap();
+        assert_eq!(find(meta, &[b"hdlr"]).unwrap()[8..16], *b"mdirappl");
+        let ilst = find(meta, &[b"ilst"]).unwrap();
+        assert_eq!(item_value(ilst, b"\xA9nam"), Some(&b"Golden Hour"[..]));
+        assert_eq!(item_value(ilst, b"\xA9ART"), Some(&b"JVKE/Other"[..]));
+        assert_eq!(
+            item_value(ilst, b"\xA9alb"),
+            Some(&b"Golden Hour Album"[..])
+        );
+        assert!(item_value(ilst, b"\xA9cmt").is_some());
+        let covr = find(ilst, &[b"covr", b"data"]).unwrap();
+        assert_eq!(covr[..4], 13u32.to_be_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_mdat_first_ok() -> Result<()> {
+        let data = build_file(false, None);
+        let output = write(
+            &data,
+            &get_tags(),
+            &TagOptions {
+                comment: false,
+                ..Default::default()
+            },
+        )?;
+        // Only the size of moov is changed before the appended udta box.
+        let moov = parse_atoms(&data)?[2].start;
+        assert_eq!(output[..moov], data[..moov]);
+        assert_eq!(output[moov + 4..data.len()], data[moov + 4..]);
+        assert_eq!(
+            read_chunks(&output),
+            [
+                b"CHUNK-ONE".to_vec(),
+                b"CHUNK-TWO".to_vec(),
+                b"CHUNK-TWO".to_vec()
+            ],
+        );
+        let ilst = find(&output, &[b"moov", b"udta", b"meta", b"ilst"]).unwrap();
+        assert!(item_value(ilst, b"\xA9cmt").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_existing_ilst_ok() -> Result<()> {
+        let mut ilst = build_item(b"\xA9nam", DATA_UTF8, b"Old").1;
+        ilst.extend_from_slice(&build_item(b"\xA9too", DATA_UTF8, b"Encoder").1);
+        ilst.extend_from_slice(&build_freeform("NETEASE_ID", b"0").1);
+        ilst.extend_from_slice(&build_freeform("iTunSMPB", b"keep").1);
+        let mut meta = full_atom(b"hdlr", b"\0\0\0\0mdirappl\0\0\0\0\0\0\0\0\0");
+        meta.extend_from_slice(&build_atom(b"ilst", &ilst));
+        let mut udta = build_atom(b"name", b"keep");
+        udta.extend_from_slice(&full_atom(b"meta", &meta));
+        let data = build_file(true, Some(udta));
+
+        let output = write(&data, &get_tags(), &TagOptions::default())?;
+        assert_eq!(
+            find(&output, &[b"moov", b"udta", b"name"]),
+            Some(&b"keep"[..])
+        );
+        let meta = find(&output, &[b"moov", b"udta", b"meta"]).unwrap();
+        assert_eq!(parse_atoms(meta)?.len(), 2);
+        let ilst = find(meta, &[b"ilst"]).unwrap();
+        let items = parse_atoms(ilst)?;
+        assert_eq!(items.len(), 12);
+        assert_eq!(item_value(ilst, b"\xA9too"), Some(&b"Encoder"[..]));
+        assert_eq!(item_value(ilst, b"\xA9nam"), Some(&b"Golden Hour"[..]));
+        assert_eq!(item_value(ilst, b"aART"), Some(&b"JVKE"[..]));
+        let freeforms = items
+            .iter()
+            .filter(|item| &item.kind == b"----")
+            .map(|item| {
+                let body = item.body(ilst);
+                let name = find(body, &[b"name"]).unwrap()[4..].to_vec();
+                let value = find(body, &[b"data"]).unwrap()[8..].to_vec();
+                (
+                    String::from_utf8(name).unwrap(),
+                    String::from_utf8(value).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            freeforms,
+            [
+                ("iTunSMPB".into(), "keep".into()),
+                ("SUBTITLE".into(), "Alias".into()),
+                ("WEBSITE".into(), "https://music.163.com/song?id=1".into()),
+                ("NETEASE_ID".into(), "1".into()),
+                ("NETEASE_ALBUMID".into(), "3".into()),
+            ],
+        );
+        let fields = read(&output)?;
+        assert_eq!(fields[0], (Field::Title, vec!["Golden Hour".to_string()]));
+        assert!(fields.contains(&(Field::Artist, vec!["JVKE/Other".to_string()])));
+        assert!(fields.contains(&(Field::NeteaseId, vec!["1".to_string()])));
+        assert_eq!(
+            read_chunks(&output),
+            [
+                b"CHUNK-ONE".to_vec(),
+                b"CHUNK-TWO".to_vec(),
+                b"CHUNK-TWO".to_vec()
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_lyrics_ok() -> Result<()> {
+        let data = build_file(true, None);
+        let tags = Tags {
+            lyrics: vec![
+                Lyrics::parse("[00:01.00]La\n[00:02.00]Di"),
+                Lyrics::parse("[00:01.00]啦"),
+            ],
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &TagOptions::default())?;
+        let ilst = find(&output, &[b"moov", b"udta", b"meta", b"ilst"]).unwrap();
+        assert_eq!(
+            item_value(ilst, b"\xA9lyr"),
+            Some("La\nDi\n\n啦".as_bytes())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_tag_policy_ok() -> Result<()> {
+        let mut ilst = build_item(b"\xA9nam", DATA_UTF8, b"Old").1;
+        ilst.extend_from_slice(&build_item(b"\xA9lyr", DATA_UTF8, b"la").1);
+        ilst.extend_from_slice(&build_freeform("replaygain_track_gain", b"-1.00 dB").1);
+        ilst.extend_from_slice(&build_item(b"covr", 14, b"old").1);
+        let mut meta = full_atom(b"hdlr", b"\0\0\0\0mdirappl\0\0\0\0\0\0\0\0\0");
+        meta.extend_from_slice(&build_atom(b"ilst", &ilst));
+        let data = build_file(true, Some(full_atom(b"meta", &meta)));
+        let tags = get_tags();
+        let [output, stripped] = fixture::write_policies(&data, &tags, write)?;
+
+        let ilst = find(&output, &[b"moov", b"udta", b"meta", b"ilst"]).unwrap();
+        assert_eq!(item_value(ilst, b"\xA9nam"), Some(&b"Golden Hour"[..]));
+        assert_eq!(item_value(ilst, b"\xA9lyr"), Some(&b"la"[..]));
+        assert_eq!(item_value(ilst, b"covr"), Some(&b"old"[..]));
+        assert!(ilst.windows(21).any(|w| w == b"replaygain_track_gain"));
+
+        let ilst = find(&stripped, &[b"moov", b"udta", b"meta", b"ilst"]).unwrap();
+        assert_eq!(item_value(ilst, b"\xA9lyr"), None);
+        assert_eq!(item_value(ilst, b"covr"), Some(&tags.image[..]));
+        assert!(!ilst.windows(21).any(|w| w == b"replaygain_track_gain"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_fill_missing_ok() -> Result<()> {
+        let ilst = build_item(b"\xA9nam", DATA_UTF8, b"Old").1;
+        let mut meta = full_atom(b"hdlr", b"\0\0\0\0mdirappl\0\0\0\0\0\0\0\0\0");
+        meta.extend_from_slice(&build_atom(b"ilst", &ilst));
+        let data = build_file(true, Some(full_atom(b"meta", &meta)));
+        let mut tags = get_tags();
+        tags.image.clear();
+        tags.comment = Some(String::new());
+        let options = TagOptions {
+            policy: TagPolicy::FillMissing,
+            ..Default::default()
+        };
+
+        let output = write(&data, &tags, &options)?;
+        let ilst = find(&output, &[b"moov", b"udta", b"meta", b"ilst"]).unwrap();
+        assert_eq!(parse_atoms(ilst)?.len(), 8);
+        assert_eq!(item_value(ilst, b"\xA9nam"), Some(&b"Old"[..]));
+        assert_eq!(item_value(ilst, b"\xA9cmt"), None);
+        assert_eq!(item_value(ilst, b"\xA9ART"), Some(&b"JVKE/Other"[..]));
+        assert!(find(ilst, &[b"covr"]).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_err() {
+        let data = build_atom(b"ftyp", b"M4A ");
+        let result = write(&data, &get_tags(), &TagOptions::default());
+        assert!(matches!(
+            result,
+            Err(Errors::Tag {
+                container: Container::Mp4,
+                ..
+            })
+        ));
+
+        let data = build_file(true, None);
+        let result = write(&data[..data.len() - 4], &get_tags(), &TagOptions::default());
+        assert!(result.is_err_and(|err| err.is_truncated()));
+    }
+}
@@ -0,0 +1,788 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use super::cover::ImageInfo;
+use super::{
+    collect_fields, get_image_mime_type, get_vorbis_comments, is_extra_comment, Container, Field,
+    Lyrics, MalformedError, TagOptions, Tags,
+};
+use crate::error::{Errors, Result, Section};
+
+const CRC_TABLE: [u32; 256] = build_crc_table();
+const PAGE_HEADER_LENGTH: usize = 27;
+const FLAG_CONTINUED: u8 = 0x01;
+const FLAG_BOS: u8 = 0x02;
+
+/// The codec of the ogg stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Codec {
+    Vorbis,
+    Opus,
+}
+
+impl Codec {
+    /// Sniff the codec by the first page of the stream.
+    pub(crate) fn sniff(head: &[u8]) -> Option<Self> {
+        let segments = *head.get(26)? as usize;
+        Self::from_packet(head.get(PAGE_HEADER_LENGTH + segments..)?)
+    }
+
+    /// Get the codec by the identification packet.
+    fn from_packet(packet: &[u8]) -> Option<Self> {
+        match packet {
+            [0x01, b'v', b'o', b'r', b'b', b'i', b's', ..] => Some(Self::Vorbis),
+            [b'O', b'p', b'u', b's', b'H', b'e', b'a', b'd', ..] => Some(Self::Opus),
+            _ => None,
+        }
+    }
+
+    /// The magic of the comment packet.
+    fn comment_magic(&self) -> &'static [u8] {
+        match self {
+            Self::Vorbis => b"\x03vorbis",
+            Self::Opus => b"OpusTags",
+        }
+    }
+
+    /// The count of header packets, the audio packets start on a fresh page after them.
+    fn header_count(&self) -> usize {
+        match self {
+            Self::Vorbis => 3,
+            Self::Opus => 2,
+        }
+    }
+
+    pub(crate) fn container(&self) -> Container {
+        match self {
+            Self::Vorbis => Container::Ogg,
+            Self::Opus => Container::Opus,
+        }
+    }
+}
+
+/// The page of the ogg stream.
+struct Page<'a> {
+    serial: u32,
+    sequence: u32,
+    lacing: &'a [u8],
+    body: &'a [u8],
+    length: usize,
+}
+
+impl<'a> Page<'a> {
+    /// Parse the page at the beginning of data.
+    fn parse(data: &'a [u8], offset: usize) -> Result<Self> {
+        let truncated = || Errors::Truncated {
+            section: Section::Audio,
+            offset: data.len() as u64,
+        };
+        let page = data.get(offset..).ok_or_else(truncated)?;
+        let header = page.get(..PAGE_HEADER_LENGTH).ok_or_else(truncated)?;
+        if &header[..4] != b"OggS" || header[4] != 0 {
+            return Err(Errors::InvalidFileType);
+        }
+        let body_start = PAGE_HEADER_LENGTH + header[26] as usize;
+        let lacing = page
+            .get(PAGE_HEADER_LENGTH..body_start)
+            .ok_or_else(truncated)?;
+        let body_length = lacing.iter().map(|size| *size as usize).sum::<usize>();
+        let body = page
+            .get(body_start..body_start + body_length)
+            .ok_or_else(truncated)?;
+        Ok(Self {
+            serial: u32::from_le_bytes([header[14], header[15], header[16], header[17]]),
+            sequence: u32::from_le_bytes([header[18], header[19], header[20], header[21]]),
+            lacing,
+            body,
+            length: body_start + body_length,
+        })
+    }
+}
+
+/// The comment packet of vorbis and opus.
+struct Comments {
+    vendor: Vec<u8>,
+    comments: Vec<Vec<u8>>,
+    /// The framing bit of vorbis, or the binary data of opus
+    rest: Vec<u8>,
+}
+
+impl Comments {
+    fn parse(packet: &[u8], codec: Codec) -> Result<Self> {
+        let malformed = || tag_error(codec, "Malformed comment packet");
+        let mut data = packet
+            .strip_prefix(codec.comment_magic())
+            .ok_or_else(malformed)?;
+        let mut read = |length: usize| -> Result<&[u8]> {
+            if data.len() < length {
+                return Err(malformed());
+            }
+            let (value, rest) = data.split_at(length);
+            data = rest;
+            Ok(value)
+        };
+        let read_u32 = |value: &[u8]| u32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+
+        let vendor_length = read_u32(read(4)?) as usize;
+        let vendor = read(vendor_length)?.to_vec();
+        let count = read_u32(read(4)?) as usize;
+        let mut comments = Vec::new();
+        for _ in 0..count {
+            let length = read_u32(read(4)?) as usize;
+            comments.push(read(length)?.to_vec());
+        }
+        Ok(Self {
+            vendor,
+            comments,
+            rest: data.to_vec(),
+        })
+    }
+
+    /// Check if the comments contain the key, the key is case insensitive.
+    fn contains(&self, key: &str) -> bool {
+        self.comments.iter().any(|comment| {
+            let name = comment.split(|c| *c == b'=').next().unwrap_or_default();
+            name.eq_ignore_ascii_case(key.as_bytes())
+        })
+    }
+
+    /// Get the values of the key, the key is case insensitive.
+    fn get(&self, key: &str) -> Vec<String> {
+        self.comments
+            .iter()
+            .filter_map(|comment| {
+                let (name, value) = comment.split_at(comment.iter().position(|c| *c == b'=')?);
+                name.eq_ignore_ascii_case(key.as_bytes())
+                    .then(|| String::from_utf8_lossy(&value[1..]).to_string())
+            })
+            .collect()
+    }
+
+    /// Retain the comments whose name matches the predicate.
+    fn retain<F>(&mut self, f: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.comments.retain(|comment| {
+            let name = comment.split(|c| *c == b'=').next().unwrap_or_default();
+            f(&String::from_utf8_lossy(name))
+        });
+    }
+
+    /// Replace the comments of the key, the key is case insensitive.
+    fn set(&mut self, key: &str, values: Vec<String>) {
+        self.retain(|name| !name.eq_ignore_ascii_case(key));
+        for value in values {
+            self.comments
+                .push(format!("{}={}", key, value).into_bytes());
+        }
+    }
+
+    fn to_packet(&self, codec: Codec) -> Vec<u8> {
+        let mut packet = codec.comment_magic().to_vec();
+        packet.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        packet.extend_from_slice(&self.vendor);
+        packet.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+        for comment in &self.comments {
+            packet.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            packet.extend_from_slice(comment);
+        }
+        packet.extend_from_slice(&self.rest);
+        packet
+    }
+}
+
+/// The header packets of the ogg stream.
+struct Headers {
+    codec: Codec,
+    serial: u32,
+    packets: Vec<Vec<u8>>,
+    /// The offset of the first page after the header pages
+    offset: usize,
+    /// The sequence number of the last header page
+    last_sequence: u32,
+}
+
+impl Headers {
+    /// Collect the header packets, they should end with a page.
+    fn parse(data: &[u8]) -> Result<Self> {
+        let first = Page::parse(data, 0)?;
+        let codec = Codec::from_packet(first.body).ok_or(Errors::InvalidFileType)?;
+        let serial = first.serial;
+
+        let mut offset = 0;
+        let mut packets = Vec::new();
+        let mut packet = Vec::new();
+        let mut last_sequence = 0;
+        while packets.len() < codec.header_count() {
+            let page = Page::parse(data, offset)?;
+            if page.serial != serial {
+                return Err(tag_error(codec, "Multiplexed stream is not supported"));
+            }
+            let mut position = 0;
+            for size in page.lacing {
+                packet.extend_from_slice(&page.body[position..position + *size as usize]);
+                position += *size as usize;
+                if *size < 255 {
+                    packets.push(std::mem::take(&mut packet));
+                }
+            }
+            offset += page.length;
+            last_sequence = page.sequence;
+        }
+        if packets.len() > codec.header_count() || !packet.is_empty() {
+            return Err(tag_error(codec, "The audio packet is on the header page"));
+        }
+        Ok(Self {
+            codec,
+            serial,
+            packets,
+            offset,
+            last_sequence,
+        })
+    }
+}
+
+/// Read the fields from the comments of the ogg vorbis or opus music.
+pub(crate) fn read(data: &[u8]) -> Result<Vec<(Field, Vec<String>)>> {
+    let headers = Headers::parse(data)?;
+    let comments = Comments::parse(&headers.packets[1], headers.codec)?;
+    Ok(collect_fields(|field| comments.get(field.vorbis_key())))
+}
+
+/// Write the comments into the ogg vorbis or opus music by the policy of the options.
+///
+/// With the `Keep` policy, the music is left untouched if it already has comments.
+/// With the default `Overwrite` policy, the mapped comments are replaced, and if the
+/// extras aren't kept, the embedded lyrics and ReplayGain comments are removed and the
+/// embedded cover is replaced. With the `FillMissing` policy, only the missing
+/// comments are written. The other comments, cover and lyrics are kept otherwise.
+///
+/// The header pages are repaginated, and the sequence numbers and checksums of the
+/// following pages are fixed up.
+pub(crate) fn write(data: &[u8], tags: &Tags, options: &TagOptions) -> Result<Vec<u8>> {
+    let Headers {
+        codec,
+        serial,
+        mut packets,
+        mut offset,
+        last_sequence,
+    } = Headers::parse(data)?;
+
+    let mut comments = Comments::parse(&packets[1], codec)?;
+    if options.keeps(!comments.comments.is_empty()) {
+        return Ok(data.to_vec());
+    }
+    if options.removes_extras() {
+        comments.retain(|name| !is_extra_comment(name));
+    }
+    let cover = comments.contains("METADATA_BLOCK_PICTURE") || comments.contains("COVERART");
+    let lyrics = comments.contains("LYRICS") || comments.contains("UNSYNCEDLYRICS");
+    let mut set = |key: &str, values: Vec<String>| {
+        if options.writes(comments.contains(key), values.concat().as_bytes()) {
+            comments.set(key, values);
+        }
+    };
+    if let Some(info) = &tags.info {
+        for (key, values) in get_vorbis_comments(info, options) {
+            set(&key, values);
+        }
+    }
+    if let Some(comment) = tags.comment.as_ref().filter(|_| options.comment) {
+        set("DESCRIPTION", vec![comment.to_string()]);
+    }
+    if !tags.lyrics.is_empty() && options.writes_extra(lyrics) {
+        comments.set("LYRICS", tags.lyrics.iter().map(Lyrics::to_lrc).collect());
+    }
+    if !tags.image.is_empty() && options.writes_extra(cover) {
+        let picture = STANDARD.encode(picture_block(&tags.image));
+        comments.retain(|name| !name.eq_ignore_ascii_case("COVERART"));
+        comments.set("METADATA_BLOCK_PICTURE", vec![picture]);
+    }
+    packets[1] = comments.to_packet(codec);
+
+    let mut output = Vec::with_capacity(data.len());
+    let mut sequence = 0;
+    write_packets(&mut output, serial, &mut sequence, &packets[..1], FLAG_BOS);
+    write_packets(&mut output, serial, &mut sequence, &packets[1..], 0);
+
+    // Fix up the sequence numbers of the following pages of this stream.
+    let delta = sequence.wrapping_sub(last_sequence.wrapping_add(1));
+    if delta == 0 {
+        output.extend_from_slice(&data[offset..]);
+        return Ok(output);
+    }
+    while offset < data.len() {
+        let Ok(page) = Page::parse(data, offset) else {
+            output.extend_from_slice(&data[offset..]);
+            break;
+        };
+        let start = output.len();
+        output.extend_from_slice(&data[offset..offset + page.length]);
+        if page.serial == serial {
+            let sequence = page.sequence.wrapping_add(delta);
+            output[start + 18..start + 22].copy_from_slice(&sequence.to_le_bytes());
+            update_crc(&mut output[start..]);
+        }
+        offset += page.length;
+    }
+    Ok(output)
+}
+
+/// Write the packets into pages, the last page is completed.
+fn write_packets(
+    output: &mut Vec<u8>,
+    serial: u32,
+    sequence: &mut u32,
+    packets: &[Vec<u8>],
+    flag: u8,
+) {
+    let mut lacing = Vec::new();
+    let mut body = Vec::new();
+    let mut header_type = flag;
+    // The granule position is -1 if no packet ends on the page.
+    let mut granule = u64::MAX;
+    for packet in packets {
+        let mut sizes = vec![255; packet.len() / 255];
+        sizes.push((packet.len() % 255) as u8);
+        let mut position = 0;
+        for (index, size) in sizes.iter().enumerate() {
+            lacing.push(*size);
+            body.extend_from_slice(&packet[position..position + *size as usize]);
+            position += *size as usize;
+            if index == sizes.len() - 1 {
+                granule = 0;
+            }
+            if lacing.len() == 255 {
+                write_page(
+                    output,
+                    header_type,
+                    granule,
+                    serial,
+                    *sequence,
+                    &lacing,
+                    &body,
+                );
+                *sequence += 1;
+                lacing.clear();
+                body.clear();
+                granule = u64::MAX;
+                header_type = match index == sizes.len() - 1 {
+                    true => 0,
+                    false => FLAG_CONTINUED,
+                };
+            }
+        }
+    }
+    if !lacing.is_empty() {
+        write_page(
+            output,
+            header_type,
+            granule,
+            serial,
+            *sequence,
+            &lacing,
+            &body,
+        );
+        *sequence += 1;
+    }
+}
+
+fn write_page(
+    output: &mut Vec<u8>,
+    header_type: u8,
+    granule: u64,
+    serial: u32,
+    sequence: u32,
+    lacing: &[u8],
+    body: &[u8],
+) {
+    let start = output.len();
+    output.extend_from_slice(b"OggS");
+    output.push(0);
+    output.push(header_type);
+    output.extend_from_slice(&granule.to_le_bytes());
+    output.extend_from_slice(&serial.to_le_bytes());
+    output.extend_from_slice(&sequence.to_le_bytes());
+    output.extend_from_slice(&[0; 4]);
+    output.push(lacing.len() as u8);
+    output.extend_from_slice(lacing);
+    output.extend_from_slice(body);
+    update_crc(&mut output[start..]);
+}
+
+/// Recompute the checksum of the page.
+fn update_crc(page: &mut [u8]) {
+    page[22..26].fill(0);
+    let crc = crc32(page);
+    page[22..26].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// The crc32 of ogg, the polynomial is 0x04c11db7 without reflection and final xor.
+fn crc32(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, byte| {
+        (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
+
+const fn build_crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut r = (i as u32) << 24;
+        let mut j = 0;
+        while j < 8 {
+            r = match r & 0x8000_0000 {
+                0 => r << 1,
+                _ => (r << 1) ^ 0x04c1_1db7,
+            };
+            j += 1;
+        }
+        table[i] = r;
+        i += 1;
+    }
+    table
+}
+
+/// Build the flac picture block of the cover for `METADATA_BLOCK_PICTURE`.
+fn picture_block(image: &[u8]) -> Vec<u8> {
+    let mime = get_image_mime_type(image);
+    let info = ImageInfo::parse(image);
+    let mut block = Vec::with_capacity(image.len() + mime.len() + 32);
+    // The front cover.
+    block.extend_from_slice(&3u32.to_be_bytes());
+    block.extend_from_slice(&(mime.len() as u32).to_be_bytes());
+    block.extend_from_slice(mime.as_bytes());
+    // The empty description.
+    block.extend_from_slice(&0u32.to_be_bytes());
+    for value in [info.width, info.height, info.depth, info.colors] {
+        block.extend_from_slice(&value.to_be_bytes());
+    }
+    block.extend_from_slice(&(image.len() as u32).to_be_bytes());
+    block.extend_from_slice(image);
+    block
+}
+
+fn tag_error(codec: Codec, message: &'static str) -> Errors {
+    Errors::Tag {
+        container: codec.container(),
+        source: Box::new(MalformedError(message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::tag::fixture::{self, get_tags};
+    use crate::tag::TagPolicy;
+
+    const SERIAL: u32 = 0x1234;
+
+    /// The header type, granule position and body of the page.
+    type RawPage = (u8, u64, Vec<u8>);
+
+    fn comment_packet(codec: Codec, comments: &[&str], rest: &[u8]) -> Vec<u8> {
+        let comments = Comments {
+            vendor: b"test vendor".to_vec(),
+            comments: comments.iter().map(|c| c.as_bytes().to_vec()).collect(),
+            rest: rest.to_vec(),
+        };
+        comments.to_packet(codec)
+    }
+
+    /// Build the stream with header pages and three audio pages.
+    fn build_stream(codec: Codec, comment: Vec<u8>) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut sequence = 0;
+        let ident = match codec {
+            Codec::Vorbis => b"\x01vorbis\x00\x00\x00\x00\x02".to_vec(),
+            Codec::Opus => b"OpusHead\x01\x02".to_vec(),
+        };
+        write_packets(&mut data, SERIAL, &mut sequence, &[ident], FLAG_BOS);
+        let mut headers = vec![comment];
+        if codec == Codec::Vorbis {
+            headers.push(b"\x05vorbis setup".to_vec());
+        }
+        write_packets(&mut data, SERIAL, &mut sequence, &headers, 0);
+        for index in 0..3u8 {
+            let flag = if index == 2 { 0x04 } else { 0 };
+            let body = vec![index; 300];
+            write_page(
+                &mut data,
+                flag,
+                960 * (index as u64 + 1),
+                SERIAL,
+                sequence,
+                &[255, 45],
+                &body,
+            );
+            sequence += 1;
+        }
+        data
+    }
+
+    /// Check the pages, and return the packets of the stream and the pages.
+    fn read_stream(data: &[u8]) -> Result<(Vec<Vec<u8>>, Vec<RawPage>)> {
+        let mut offset = 0;
+        let mut packets = Vec::new();
+        let mut packet = Vec::new();
+        let mut pages = Vec::new();
+        while offset < data.len() {
+            let page = Page::parse(data, offset)?;
+            let mut raw = data[offset..offset + page.length].to_vec();
+            let crc = raw[22..26].to_vec();
+            update_crc(&mut raw);
+            assert_eq!(raw[22..26], crc);
+            assert_eq!(page.serial, SERIAL);
+            assert_eq!(page.sequence as usize, pages.len());
+
+            let mut position = 0;
+            for size in page.lacing {
+                packet.extend_from_slice(&page.body[position..position + *size as usize]);
+                position += *size as usize;
+                if *size < 255 {
+                    packets.push(std::mem::take(&mut packet));
+                }
+            }
+            let granule = u64::from_le_bytes(raw[6..14].try_into()?);
+            pages.push((raw[5], granule, page.body.to_vec()));
+            offset += page.length;
+        }
+        Ok((packets, pages))
+    }
+
+    #[test]
+    fn test_crc32_ok() {
+        assert_eq!(crc32(b"123456789"), 0x89A1_897F);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_sniff_ok() {
+        let data = build_stream(Codec::Vorbis, comment_packet(Codec::Vorbis, &[], &[1]));
+        assert_eq!(Container::sniff(&data), Some(Container::Ogg));
+        let data = build_stream(Codec::Opus, comment_packet(Codec::Opus, &[], &[]));
+        assert_eq!(Container::sniff(&data), Some(Container::Opus));
+        assert_eq!(Container::sniff(b"OggS"), None);
+    }
+
+    #[test]
+    fn test_write_vorbis_ok() -> Result<()> {
+        let comment = comment_packet(Codec::Vorbis, &["title=Old", "ENCODER=test"], &[1]);
+        let data = build_stream(Codec::Vorbis, comment);
+        let output = write(
+            &data,
+            &Tags {
+                image: Vec::new(),
+                ..get_tags()
+            },
+            &TagOptions::default(),
+        )?;
+
+        let (packets, pages) = read_stream(&output)?;
+        assert_eq!(packets.len(), 6);
+        assert_eq!(packets[2], b"\x05vorbis setup");
+        let comments = Comments::parse(&packets[1], Codec::Vorbis)?;
+        assert_eq!(comments.vendor, b"test vendor");
+        assert_eq!(comments.rest, [1]);
+        let comments: Vec<_> = comments
+            .comments
+            .iter()
+            .map(|c| String::from_utf8_lossy(c).to_string())
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                "ENCODER=test",
+                "TITLE=Golden Hour",
+                "ALBUM=Golden Hour Album",
+                "ARTIST=JVKE",
+                "ARTIST=Other",
+                "ALBUMARTIST=JVKE",
+                "SUBTITLE=Alias",
+                "WEBSITE=https://music.163.com/song?id=1",
+                "NETEASE_ID=1",
+                "NETEASE_ALBUMID=3",
+                "DESCRIPTION=163 key(Don't modify):abc",
+            ],
+        );
+        let fields = read(&output)?;
+        assert_eq!(
+            fields[2],
+            (Field::Artist, vec!["JVKE".to_string(), "Other".to_string()])
+        );
+        assert_eq!(pages.len(), 5);
+        assert_eq!(pages[0].0, FLAG_BOS);
+        assert_eq!(pages[4], (0x04, 2880, vec![2; 300]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_fill_missing_ok() -> Result<()> {
+        let comment = comment_packet(Codec::Opus, &["title=Old", "ENCODER=test"], &[]);
+        let data = build_stream(Codec::Opus, comment);
+        let options = TagOptions {
+            policy: TagPolicy::FillMissing,
+            ..Default::default()
+        };
+        let output = write(
+            &data,
+            &Tags {
+                image: Vec::new(),
+                ..get_tags()
+            },
+            &options,
+        )?;
+
+        let (packets, _) = read_stream(&output)?;
+        let comments = Comments::parse(&packets[1], Codec::Opus)?;
+        let comments: Vec<_> = comments
+            .comments
+            .iter()
+            .map(|c| String::from_utf8_lossy(c).to_string())
+            .collect();
+        assert_eq!(
+            comments[..3],
+            ["title=Old", "ENCODER=test", "ALBUM=Golden Hour Album"]
+        );
+        assert_eq!(comments.len(), 11);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_lyrics_ok() -> Result<()> {
+        let comment = comment_packet(Codec::Vorbis, &["UNSYNCEDLYRICS=Old"], &[0x01]);
+        let data = build_stream(Codec::Vorbis, comment);
+        let tags = Tags {
+            lyrics: vec![Lyrics::parse("[la:eng]\n[00:01.5]La")],
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &TagOptions::default())?;
+        let (packets, _) = read_stream(&output)?;
+        let comments = Comments::parse(&packets[1], Codec::Vorbis)?;
+        assert_eq!(comments.comments, [b"UNSYNCEDLYRICS=Old".to_vec()]);
+
+        let options = TagOptions {
+            keep_extras: false,
+            ..Default::default()
+        };
+        let output = write(&data, &tags, &options)?;
+        let (packets, _) = read_stream(&output)?;
+        let comments = Comments::parse(&packets[1], Codec::Vorbis)?;
+        assert_eq!(
+            comments.comments,
+            [b"LYRICS=[la:eng]\n[00:01.50]La".to_vec()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_tag_policy_ok() -> Result<()> {
+        let comment = comment_packet(
+            Codec::Opus,
+            &[
+                "TITLE=Old",
+                "REPLAYGAIN_TRACK_GAIN=-1.00 dB",
+                "LYRICS=la",
+                "METADATA_BLOCK_PICTURE=old",
+            ],
+            &[],
+        );
+        let data = build_stream(Codec::Opus, comment);
+        let tags = get_tags();
+        let parse = |output: &[u8]| -> Result<Vec<String>> {
+            let (packets, _) = read_stream(output)?;
+            let comments = Comments::parse(&packets[1], Codec::Opus)?;
+            Ok(comments
+                .comments
+                .iter()
+                .map(|c| String::from_utf8_lossy(c).to_string())
+                .collect())
+        };
+        let [output, stripped] = fixture::write_policies(&data, &tags, write)?;
+
+        let comments = parse(&output)?;
+        assert_eq!(
+            comments[..3],
+            [
+                "REPLAYGAIN_TRACK_GAIN=-1.00 dB",
+                "LYRICS=la",
+                "METADATA_BLOCK_PICTURE=old"
+            ]
+        );
+        assert!(comments.contains(&"TITLE=Golden Hour".to_string()));
+
+        let comments = parse(&stripped)?;
+        assert!(comments.iter().all(|c| !c.starts_with("REPLAYGAIN_")));
+        assert!(comments.iter().all(|c| !c.starts_with("LYRICS=")));
+        assert!(!comments.contains(&"METADATA_BLOCK_PICTURE=old".to_string()));
+        assert!(comments
+            .iter()
+            .any(|c| c.starts_with("METADATA_BLOCK_PICTURE=")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_large_cover_ok() -> Result<()> {
+        let data = build_stream(Codec::Opus, comment_packet(Codec::Opus, &[], &[0x01, 0xFF]));
+        let mut image = vec![0xFF, 0xD8, 0xFF, 0xE0];
+        image.resize(100_000, 0x42);
+        let options = TagOptions {
+            comment: false,
+            ..Default::default()
+        };
+        let output = write(
+            &data,
+            &Tags {
+                image: image.clone(),
+                ..get_tags()
+            },
+            &options,
+        )?;
+
+        let (packets, pages) = read_stream(&output)?;
+        assert_eq!(packets.len(), 5);
+        let comments = Comments::parse(&packets[1], Codec::Opus)?;
+        assert_eq!(comments.rest, [0x01, 0xFF]);
+        let picture = comments.comments.last().unwrap();
+        let picture = STANDARD.decode(picture.strip_prefix(b"METADATA_BLOCK_PICTURE=").unwrap())?;
+        assert_eq!(picture, picture_block(&image));
+        assert_eq!(picture[8..18], *b"image/jpeg");
+        assert!(!comments
+            .comments
+            .iter()
+            .any(|c| c.starts_with(b"DESCRIPTION")));
+
+        // The comment packet spans pages, the audio pages are renumbered.
+        let headers = pages.len() - 3;
+        assert!(headers > 3);
+        assert_eq!(pages[1].0, 0);
+        assert_eq!(pages[1].1, u64::MAX);
+        for page in &pages[2..headers] {
+            assert_eq!(page.0, FLAG_CONTINUED);
+        }
+        assert_eq!(pages[headers - 1].1, 0);
+        assert_eq!(pages[headers], (0, 960, vec![0; 300]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_err() -> Result<()> {
+        let data = build_stream(Codec::Vorbis, b"\x03vorbis\xFF".to_vec());
+        let result = write(&data, &Tags::default(), &TagOptions::default());
+        assert!(matches!(
+            result,
+            Err(Errors::Tag {
+                container: Container::Ogg,
+                ..
+            })
+        ));
+
+        let data = build_stream(Codec::Vorbis, comment_packet(Codec::Vorbis, &[], &[1]));
+        let result = write(&data[..50], &Tags::default(), &TagOptions::default());
+        assert!(result.is_err_and(|err| err.is_truncated()));
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+//! The tokenizer of the templates, like `{artist[0]} - {name}` or
+//! `{album_artist|artist}/{title:40}`.
+//!
+//! A variable can be indexed like `{artist[0]}`, fall back to the other variables like
+//! `{album_artist|artist}`, and be truncated to the number of characters like
+//! `{title:40}`. Use `{{` and `}}` to write the braces. The names of variables aren't
+//! checked, they're checked by the users of the templates.
+
+/// The segment of the template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Segment {
+    /// The text, the escaped braces are unescaped
+    Text(String),
+    /// The variable
+    Variable(Variable),
+}
+
+/// The variable of the template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Variable {
+    /// The names of the alternative variables with their indexes
+    pub alternatives: Vec<(String, Option<usize>)>,
+    /// The maximum characters of the value
+    pub limit: Option<usize>,
+}
+
+impl Variable {
+    /// Render the variable by the first alternative which has a value, and truncate the
+    /// value to the maximum characters. It's none if none of the alternatives has value.
+    pub fn render<F>(&self, mut get: F) -> Option<String>
+    where
+        F: FnMut(&str, Option<usize>) -> Option<String>,
+    {
+        let value = self
+            .alternatives
+            .iter()
+            .find_map(|(name, index)| get(name, *index))?;
+        match self.limit {
+            Some(limit) => Some(value.chars().take(limit).collect()),
+            None => Some(value),
+        }
+    }
+}
+
+/// Parse the template into segments.
+///
+/// # Example
+///
+/// ```rust
+/// # use ncmdump::tag::template::{self, Segment, Variable};
+/// #
+/// let segments = template::parse("{{{artist[0]|name:4}}}").unwrap();
+/// assert_eq!(
+///     segments,
+///     [
+///         Segment::Text("{".into()),
+///         Segment::Variable(Variable {
+///             alternatives: vec![("artist".into(), Some(0)), ("name".into(), None)],
+///             limit: Some(4),
+///         }),
+///         Segment::Text("}".into()),
+///     ],
+/// );
+/// ```
+pub fn parse(source: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if let Some(escaped) = rest.strip_prefix("{{").or(rest.strip_prefix("}}")) {
+            text.push(c);
+            rest = escaped;
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '{' => {
+                let (variable, after) = rest
+                    .split_once('}')
+                    .ok_or_else(|| format!("unclosed variable in {:?}", source))?;
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(Segment::Variable(parse_variable(variable)?));
+                rest = after;
+            }
+            '}' => return Err(format!("unmatched `}}` in {:?}", source)),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+/// Parse the variable like `name`, `artist[0]`, `album_artist|artist` or `title:40`.
+fn parse_variable(variable: &str) -> Result<Variable, String> {
+    let (names, limit) = match variable.split_once(':') {
+        Some((names, limit)) => {
+            let limit = limit
+                .parse()
+                .ok()
+                .filter(|limit| *limit > 0)
+                .ok_or_else(|| format!("invalid length of variable `{}`", variable))?;
+            (names, Some(limit))
+        }
+        None => (variable, None),
+    };
+    let mut alternatives = Vec::new();
+    for name in names.split('|') {
+        let (name, index) = match name.split_once('[') {
+            Some((name, index)) => {
+                let index = index
+                    .strip_suffix(']')
+                    .and_then(|index| index.parse().ok())
+                    .ok_or_else(|| format!("invalid index of variable `{}`", variable))?;
+                (name, Some(index))
+            }
+            None => (name, None),
+        };
+        alternatives.push((name.trim().to_string(), index));
+    }
+    Ok(Variable {
+        alternatives,
+        limit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ok() {
+        let variable = |name: &str, index| {
+            Segment::Variable(Variable {
+                alternatives: vec![(name.to_string(), index)],
+                limit: None,
+            })
+        };
+        assert_eq!(
+            parse("{{{name}}} - {alias[1]}"),
+            Ok(vec![
+                Segment::Text("{".into()),
+                variable("name", None),
+                Segment::Text("} - ".into()),
+                variable("alias", Some(1)),
+            ]),
+        );
+        assert_eq!(parse(""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_parse_err() {
+        let sources = [
+            "{name",
+            "name}",
+            "{alias[a]}",
+            "{alias[0}",
+            "{name:0}",
+            "{name:a}",
+        ];
+        for source in sources {
+            assert!(parse(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_render_variable_ok() -> Result<(), String> {
+        let [Segment::Variable(variable)] = &parse("{subtitle|title[1]|name:6}")?[..] else {
+            return Err("not a variable".into());
+        };
+        let values = [("title", "Golden Hour"), ("name", "Golden Hour")];
+        let get = |name: &str, index: Option<usize>| {
+            values
+                .iter()
+                .find(|(n, _)| *n == name && index.is_none())
+                .map(|(_, value)| value.to_string())
+        };
+        assert_eq!(variable.render(get), Some("Golden".into()));
+        assert_eq!(variable.render(|_, _| None), None);
+        Ok(())
+    }
+}
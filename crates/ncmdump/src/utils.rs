@@ -1,5 +1,7 @@
+#[cfg(feature = "std")]
 use std::io::Read;
 
+#[cfg(feature = "std")]
 use crate::error::Result;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -28,6 +30,7 @@ impl FileType {
     /// let mut file = File::open("res/test.ncm").unwrap();
     /// let file_type = FileType::parse(&mut file).unwrap();
     /// ```
+    #[cfg(feature = "std")]
     pub fn parse<R>(reader: &mut R) -> Result<Self>
     where
         R: Read,
@@ -36,17 +39,29 @@ impl FileType {
         if reader.read(&mut head)? != 8 {
             return Ok(FileType::Other);
         }
+        Ok(Self::from_bytes(&head))
+    }
 
-        let file_type = match head[..] {
+    /// Return the file type of the beginning bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ncmdump::utils::FileType;
+    /// #
+    /// let file_type = FileType::from_bytes(b"CTENFDAM");
+    /// assert_eq!(file_type, FileType::Ncm);
+    /// ```
+    pub fn from_bytes(head: &[u8]) -> Self {
+        match head {
             #[cfg(feature = "ncmdump")]
-            [0x43, 0x54, 0x45, 0x4E, 0x46, 0x44, 0x41, 0x4D] => FileType::Ncm,
+            [0x43, 0x54, 0x45, 0x4E, 0x46, 0x44, 0x41, 0x4D, ..] => FileType::Ncm,
             #[cfg(feature = "qmcdump")]
-            [0xA5, 0x06, 0xB7, 0x89, _, _, _, _] => FileType::Qmc,
+            [0xA5, 0x06, 0xB7, 0x89, _, _, _, _, ..] => FileType::Qmc,
             #[cfg(feature = "qmcdump")]
-            [0x8A, 0x0E, 0xE5, _, _, _, _, _] => FileType::Qmc,
+            [0x8A, 0x0E, 0xE5, _, _, _, _, _, ..] => FileType::Qmc,
             _ => FileType::Other,
-        };
-        Ok(file_type)
+        }
     }
 }
 
@@ -63,6 +78,7 @@ impl FileType {
 /// let mut file = File::open("res/test.ncm").unwrap();
 /// let file_type = get_file_type(&mut file).unwrap();
 /// ```
+#[cfg(feature = "std")]
 #[deprecated]
 pub fn get_file_type<R>(reader: &mut R) -> Result<FileType>
 where
@@ -84,7 +100,7 @@ where
 /// let mut file = File::open("res/test.ncm").unwrap();
 /// let result = is_ncm_file(&mut file).unwrap();
 /// ```
-#[cfg(feature = "ncmdump")]
+#[cfg(all(feature = "ncmdump", feature = "std"))]
 pub fn is_ncm_file<R>(reader: &mut R) -> Result<bool>
 where
     R: Read,
@@ -106,7 +122,7 @@ where
 /// let mut file = File::open("res/test.ncm").unwrap();
 /// let result = is_qmc_file(&mut file).unwrap();
 /// ```
-#[cfg(feature = "qmcdump")]
+#[cfg(all(feature = "qmcdump", feature = "std"))]
 pub fn is_qmc_file<R>(reader: &mut R) -> Result<bool>
 where
     R: Read,
@@ -115,7 +131,7 @@ where
     Ok(file_type == FileType::Qmc)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use std::fs::File;
     use std::io::Error;